@@ -0,0 +1,88 @@
+use bevy::audio::Volume;
+
+use crate::prelude::*;
+
+const MUSIC: &str = "music/background.wav";
+// Music is kept below the sound effects
+const MUSIC_VOLUME: f32 = 0.5;
+
+// Sound played on a game event, sent by the systems handling these events
+#[derive(Debug, Event, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEffect {
+    Slide,
+    Rotate,
+    Flip,
+    // Action refused, like moving a wall or a tile that is not next to the hole
+    Invalid,
+    MenuMove,
+    MenuSelect,
+    Solved,
+}
+impl SoundEffect {
+    fn path(&self) -> &'static str {
+        match self {
+            SoundEffect::Slide => "sounds/slide.wav",
+            SoundEffect::Rotate => "sounds/rotate.wav",
+            SoundEffect::Flip => "sounds/flip.wav",
+            SoundEffect::Invalid => "sounds/invalid.wav",
+            SoundEffect::MenuMove => "sounds/menu_move.wav",
+            SoundEffect::MenuSelect => "sounds/menu_select.wav",
+            SoundEffect::Solved => "sounds/solved.wav",
+        }
+    }
+}
+
+// Tag component for the entity playing the background music
+#[derive(Component)]
+pub struct BackgroundMusic;
+
+pub fn play_sound_effects(
+    mut commands: Commands,
+    mut events: EventReader<SoundEffect>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+) {
+    for sound_effect in events.read() {
+        if settings.volume() > 0. {
+            commands.spawn(AudioBundle {
+                source: asset_server.load(sound_effect.path()),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.volume())),
+            });
+        }
+    }
+}
+
+// Starts, stops and sets the volume of the background music when settings change
+pub fn update_background_music(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut music: Query<(Entity, Option<&AudioSink>, &mut PlaybackSettings), With<BackgroundMusic>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let volume = MUSIC_VOLUME * settings.volume();
+    match (music.get_single_mut(), settings.music) {
+        (Err(_), true) => {
+            commands.spawn((
+                AudioBundle {
+                    source: asset_server.load(MUSIC),
+                    settings: PlaybackSettings::LOOP.with_volume(Volume::new(volume)),
+                },
+                BackgroundMusic,
+            ));
+        }
+        (Ok((entity, _, _)), false) => {
+            commands.entity(entity).despawn();
+        }
+        (Ok((_, Some(sink), _)), true) => {
+            sink.set_volume(volume);
+        }
+        // The music is still loading, it will start with the new volume
+        (Ok((_, None, mut playback_settings)), true) => {
+            playback_settings.volume = Volume::new(volume);
+        }
+        (Err(_), false) => {}
+    }
+}
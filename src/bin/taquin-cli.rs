@@ -0,0 +1,255 @@
+use std::time::Instant;
+
+use taquinplus::prelude::*;
+
+const USAGE: &str = "\
+Usage: taquin-cli <COMMAND> [OPTIONS]
+
+Puzzles are created without any window, from the same options as taquinplus.
+
+Commands:
+  generate                   Print scrambled puzzles
+  solve                      Print the moves solving a puzzle, in the move notation
+  verify --moves <MOVES>     Check that moves solve a puzzle, written in the move notation
+                             like \"U L a@2,3 + X\"
+  bench                      Solve puzzles and print solver statistics
+
+Puzzle options:
+  --size <WIDTH>x<HEIGHT>    Size of the grid, from 2x2 to 20x20
+  --seed <NUMBER>            Seed of the scramble, random when not given
+  --actions <ACTIONS>        Comma separated actions used to scramble: slide,rotate,flip
+  --hole <random|corner>     Position of the hole
+  --mode <MODE>              classic, torus, hex, loopover, double-sided, expert,
+                             generated or daily
+  --state <STATE>            Puzzle state written in the text format, like 3x3:1,2,3/4,.,5/7,8,6R
+
+Other options:
+  --count <NUMBER>           Number of puzzles to generate or solve, with consecutive seeds
+  --max-nodes <NUMBER>       Maximum number of nodes expanded by the solver
+  --help                     Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Generate,
+    Solve,
+    Verify,
+    Bench,
+}
+
+struct Options {
+    command: Command,
+    new_puzzle: NewPuzzleOptions,
+    count: u64,
+    solver: Solver,
+    // Moves of verify, in the move notation
+    moves: String,
+}
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut args = args.into_iter();
+        let command = match args.next().as_deref() {
+            Some("generate") => Command::Generate,
+            Some("solve") => Command::Solve,
+            Some("verify") => Command::Verify,
+            Some("bench") => Command::Bench,
+            Some("--help" | "-h") => return Ok(None),
+            Some(command) => return Err(format!("Unknown command {}", command)),
+            None => return Err("Missing command".to_string()),
+        };
+        let mut count = 1;
+        let mut solver = Solver::default();
+        let mut moves = None;
+        // Puzzle options are left to the game command line parser
+        let mut puzzle_args = vec![];
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+            match name.as_str() {
+                "--count" => {
+                    let value = value()?;
+                    count = value
+                        .parse()
+                        .map_err(|_| format!("Invalid count {}", value))?;
+                }
+                "--max-nodes" => {
+                    let value = value()?;
+                    solver.max_nodes = value
+                        .parse()
+                        .map_err(|_| format!("Invalid maximum number of nodes {}", value))?;
+                }
+                "--moves" => moves = Some(value()?),
+                "--image" | "--window" => return Err(format!("Unknown option {}", name)),
+                _ => puzzle_args.push(arg),
+            }
+        }
+        let cli_options = CliOptions::parse(puzzle_args)?;
+        if cli_options.help {
+            return Ok(None);
+        }
+        let mut new_puzzle = cli_options
+            .new_puzzle(&NewPuzzleSettings::default())
+            .unwrap_or_else(|| {
+                NewPuzzleOptions::new(PuzzleVariant::Classic, &NewPuzzleSettings::default())
+            });
+        new_puzzle.seed = new_puzzle.seed.or_else(|| Some(rand::random()));
+        let moves = match (command, moves) {
+            (Command::Verify, None) => return Err("Missing --moves for verify".to_string()),
+            (_, moves) => moves.unwrap_or_default(),
+        };
+        Ok(Some(Self {
+            command,
+            new_puzzle,
+            count,
+            solver,
+            moves,
+        }))
+    }
+    // Puzzles of all the requested seeds, each with its seed
+    fn puzzles(&self) -> impl Iterator<Item = (u64, Puzzle)> + '_ {
+        let first_seed = self.new_puzzle.seed.expect("Seed is always set");
+        (0..self.count).map(move |i| {
+            let seed = first_seed.wrapping_add(i);
+            let mut new_puzzle = self.new_puzzle.clone();
+            new_puzzle.seed = Some(seed);
+            (seed, new_puzzle.create_puzzle(Handle::default()))
+        })
+    }
+}
+
+// Grid of the puzzle, top row first. Tiles show their number in reading order when solved,
+// followed by r for a rotated tile, f for a flipped one, and # for a wall.
+fn format_board(puzzle: &Puzzle) -> String {
+    let (height, width) = puzzle.size();
+    let cell_width = (height * width).to_string().len() + 1;
+    (0..height)
+        .rev()
+        .map(|row| {
+            (0..width)
+                .map(|column| {
+                    let cell = match &puzzle.tiles[(row, column)] {
+                        None => ".".to_string(),
+                        Some(tile) => {
+                            let number =
+                                (height - 1 - tile.position.0) * width + tile.position.1 + 1;
+                            let mut cell = number.to_string();
+                            if tile.fixed {
+                                cell.push('#');
+                            }
+                            if tile.is_rotated() {
+                                cell.push('r');
+                            }
+                            if tile.is_flipped() {
+                                cell.push('f');
+                            }
+                            cell
+                        }
+                    };
+                    format!("{:>width$}", cell, width = cell_width + 1)
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut failed = false;
+    match options.command {
+        Command::Generate => {
+            for (seed, puzzle) in options.puzzles() {
+                println!(
+                    "seed {}\n{}\n{}\n",
+                    seed,
+                    puzzle.to_text(),
+                    format_board(&puzzle)
+                );
+            }
+        }
+        Command::Solve => {
+            for (seed, puzzle) in options.puzzles() {
+                println!("seed {}", seed);
+                match options.solver.solve(&puzzle) {
+                    Ok(solution) => println!(
+                        "{}\n{} actions, {} moves, {} nodes\n",
+                        format_moves(&solution.actions, puzzle.size()),
+                        solution.actions.len(),
+                        solution.moves,
+                        solution.expanded_nodes
+                    ),
+                    Err(err) => {
+                        println!("not solved: {}\n", err);
+                        failed = true;
+                    }
+                }
+            }
+        }
+        Command::Verify => {
+            for (seed, mut puzzle) in options.puzzles() {
+                let actions = match parse_moves(&options.moves, puzzle.size()) {
+                    Ok(actions) => actions,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        std::process::exit(2);
+                    }
+                };
+                for action in actions {
+                    puzzle.apply_action(action);
+                }
+                if puzzle.is_solved {
+                    println!("seed {}: solved in {} actions", seed, puzzle.actions_count);
+                } else {
+                    println!("seed {}: not solved\n{}", seed, format_board(&puzzle));
+                    failed = true;
+                }
+            }
+        }
+        Command::Bench => {
+            let (mut solved, mut nodes, mut actions) = (0, 0, 0);
+            let start = Instant::now();
+            for (seed, puzzle) in options.puzzles() {
+                match options.solver.solve(&puzzle) {
+                    Ok(solution) => {
+                        solved += 1;
+                        nodes += solution.expanded_nodes;
+                        actions += solution.actions.len();
+                    }
+                    Err(err) => println!("seed {}: {}", seed, err),
+                }
+            }
+            let elapsed = start.elapsed();
+            println!("solved {}/{}", solved, options.count);
+            if solved > 0 {
+                println!(
+                    "average: {:.1} actions, {:.0} nodes, {:.1} ms",
+                    actions as f64 / solved as f64,
+                    nodes as f64 / solved as f64,
+                    elapsed.as_secs_f64() * 1000. / options.count as f64
+                );
+            }
+            failed = solved < options.count;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
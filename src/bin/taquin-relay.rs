@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+
+use taquinplus::prelude::*;
+
+const USAGE: &str = "\
+Usage: taquin-relay [OPTIONS]
+
+Relay of online races. Players join with taquinplus --race <HOST:PORT>, and a race starts
+once enough of them are waiting, all with the same scrambled puzzle.
+
+Options:
+  --bind <HOST:PORT>         Address to listen on, 127.0.0.1:7777 by default
+  --players <NUMBER>         Number of players of each race, 2 by default
+  --size <WIDTH>x<HEIGHT>    Size of the grid, 4x4 by default
+  --mode <MODE>              classic, torus, hex, loopover, double-sided, expert or generated
+  --actions <ACTIONS>        Comma separated actions used to scramble: slide,rotate,flip
+  --hole <random|corner>     Position of the hole
+  --jigsaw <on|off>          Jigsaw edges on square puzzles, off by default
+  --help                     Print this help
+";
+
+struct Options {
+    bind: String,
+    players: usize,
+    // Config of the races, with a new seed for each race
+    config: RaceConfig,
+}
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut options = Options {
+            bind: "127.0.0.1:7777".to_string(),
+            players: 2,
+            config: RaceConfig {
+                size: "4x4".to_string(),
+                seed: 0,
+                mode: None,
+                actions: None,
+                hole: None,
+                jigsaw: false,
+            },
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if name == "--help" || name == "-h" {
+                return Ok(None);
+            }
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing value for {}", name))?;
+            match name.as_str() {
+                "--bind" => options.bind = value,
+                "--players" => {
+                    options.players = value
+                        .parse()
+                        .ok()
+                        .filter(|players| *players > 0)
+                        .ok_or_else(|| format!("Invalid players {}", value))?;
+                }
+                "--size" => options.config.size = value,
+                "--mode" => options.config.mode = Some(value),
+                "--actions" => options.config.actions = Some(value),
+                "--hole" => options.config.hole = Some(value),
+                "--jigsaw" => {
+                    options.config.jigsaw = match value.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("Invalid jigsaw {}, expected on or off", value)),
+                    }
+                }
+                _ => return Err(format!("Unknown option {}", name)),
+            }
+        }
+        // Checked like the game will, before any player joins
+        options.config.new_puzzle()?;
+        Ok(Some(options))
+    }
+}
+
+enum RelayEvent {
+    Connected(usize, Sender<String>),
+    Request(usize, Result<RaceRequest, String>),
+    Disconnected(usize),
+}
+
+// Players and races, a single race being run at a time
+struct Relay {
+    options: Options,
+    players: BTreeMap<usize, Sender<String>>,
+    // Players who joined the next race, in join order
+    waiting: Vec<usize>,
+    // Players of the current race, until it is won
+    racing: Vec<usize>,
+    // Last actions count of each racing player
+    actions_counts: BTreeMap<usize, usize>,
+}
+impl Relay {
+    fn send(&self, player: usize, message: &RaceMessage) {
+        let message = serde_json::to_string(message).expect("Oops");
+        // Gone players are removed once their connection is closed
+        if let Some(sender) = self.players.get(&player) {
+            let _ = sender.send(message);
+        }
+    }
+    fn send_racing(&self, message: &RaceMessage) {
+        for player in self.racing.iter() {
+            self.send(*player, message);
+        }
+    }
+    fn handle(&mut self, event: RelayEvent) {
+        match event {
+            RelayEvent::Connected(player, sender) => {
+                println!("Player {} connected", player);
+                self.players.insert(player, sender);
+                self.send(player, &RaceMessage::Welcome { player });
+            }
+            RelayEvent::Request(player, Err(message)) => {
+                self.send(player, &RaceMessage::Error { message });
+            }
+            RelayEvent::Request(player, Ok(RaceRequest::Join)) => {
+                // Joining the next race gives up the current one
+                self.leave(player);
+                self.waiting.push(player);
+                println!("Player {} joined", player);
+                self.start_race();
+            }
+            RelayEvent::Request(
+                player,
+                Ok(RaceRequest::Progress {
+                    progress,
+                    actions_count,
+                }),
+            ) => {
+                if self.racing.contains(&player) {
+                    self.actions_counts.insert(player, actions_count);
+                    self.send_racing(&RaceMessage::Progress {
+                        player,
+                        progress,
+                        actions_count,
+                    });
+                }
+            }
+            RelayEvent::Request(player, Ok(RaceRequest::Solved { actions_count })) => {
+                if self.racing.contains(&player) {
+                    println!("Player {} won in {} actions", player, actions_count);
+                    self.send_racing(&RaceMessage::Winner {
+                        player,
+                        actions_count,
+                    });
+                    self.end_race();
+                }
+            }
+            RelayEvent::Disconnected(player) => {
+                println!("Player {} disconnected", player);
+                self.leave(player);
+                self.players.remove(&player);
+            }
+        }
+    }
+    fn leave(&mut self, player: usize) {
+        self.waiting.retain(|waiting| *waiting != player);
+        if self.racing.contains(&player) {
+            self.racing.retain(|racing| *racing != player);
+            self.send_racing(&RaceMessage::Left { player });
+            // A race needs 2 players, the last one wins it
+            if self.racing.len() < 2 {
+                if let Some(winner) = self.racing.first().copied() {
+                    println!("Player {} won, the others left", winner);
+                    self.send(
+                        winner,
+                        &RaceMessage::Winner {
+                            player: winner,
+                            actions_count: self.actions_counts.get(&winner).copied().unwrap_or(0),
+                        },
+                    );
+                }
+                self.end_race();
+            }
+        }
+    }
+    fn end_race(&mut self) {
+        self.racing.clear();
+        self.actions_counts.clear();
+        self.start_race();
+    }
+    // Starts a race with the first waiting players, once the previous race is over
+    fn start_race(&mut self) {
+        if !self.racing.is_empty() || self.waiting.len() < self.options.players {
+            return;
+        }
+        self.racing = self.waiting.drain(..self.options.players).collect();
+        let config = RaceConfig {
+            seed: rand::random(),
+            ..self.options.config.clone()
+        };
+        println!(
+            "Race of players {:?} started, seed {}",
+            self.racing, config.seed
+        );
+        self.send_racing(&RaceMessage::Start {
+            players: self.racing.clone(),
+            config,
+        });
+    }
+}
+
+fn serve_player(
+    player: usize,
+    stream: TcpStream,
+    events: Sender<RelayEvent>,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let (messages, messages_receiver) = channel::<String>();
+    let _ = events.send(RelayEvent::Connected(player, messages));
+    std::thread::spawn(move || {
+        for message in messages_receiver {
+            if writeln!(writer, "{}", message).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = serde_json::from_str(&line).map_err(|err| err.to_string());
+            if events.send(RelayEvent::Request(player, request)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(RelayEvent::Disconnected(player));
+    });
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let listener = match TcpListener::bind(&options.bind) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Can't listen on {}: {}", options.bind, err);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Relay listening on {}, {} players per race",
+        options.bind, options.players
+    );
+    let (events, events_receiver) = channel();
+    std::thread::spawn(move || {
+        // Players are numbered from 1, as shown in the game
+        for (player, stream) in (1..).zip(listener.incoming().flatten()) {
+            if let Err(err) = serve_player(player, stream, events.clone()) {
+                eprintln!("Player {} failed: {}", player, err);
+            }
+        }
+    });
+    let mut relay = Relay {
+        options,
+        players: BTreeMap::new(),
+        waiting: vec![],
+        racing: vec![],
+        actions_counts: BTreeMap::new(),
+    };
+    for event in events_receiver {
+        relay.handle(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Receiver;
+
+    use super::*;
+
+    fn new_relay() -> Relay {
+        Relay {
+            options: Options::parse(vec![]).expect("Oops").expect("Oops"),
+            players: BTreeMap::new(),
+            waiting: vec![],
+            racing: vec![],
+            actions_counts: BTreeMap::new(),
+        }
+    }
+
+    fn connect(relay: &mut Relay, player: usize) -> Receiver<String> {
+        let (sender, receiver) = channel();
+        relay.handle(RelayEvent::Connected(player, sender));
+        relay.handle(RelayEvent::Request(player, Ok(RaceRequest::Join)));
+        receiver
+    }
+
+    // Messages received by a player since the last call
+    fn received(receiver: &Receiver<String>) -> Vec<RaceMessage> {
+        receiver
+            .try_iter()
+            .map(|message| serde_json::from_str(&message).expect("Oops"))
+            .collect()
+    }
+
+    fn started(messages: &[RaceMessage]) -> Option<Vec<usize>> {
+        messages.iter().find_map(|message| match message {
+            RaceMessage::Start { players, .. } => Some(players.clone()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn races_go_on_after_players_disconnect() {
+        let mut relay = new_relay();
+        let player_1 = connect(&mut relay, 1);
+        let player_2 = connect(&mut relay, 2);
+        assert_eq!(started(&received(&player_1)), Some(vec![1, 2]));
+        assert_eq!(started(&received(&player_2)), Some(vec![1, 2]));
+        relay.handle(RelayEvent::Request(
+            1,
+            Ok(RaceRequest::Progress {
+                progress: 0.5,
+                actions_count: 12,
+            }),
+        ));
+        let player_3 = connect(&mut relay, 3);
+        assert_eq!(started(&received(&player_3)), None);
+
+        // The last player of the race wins it once the other one is gone
+        relay.handle(RelayEvent::Disconnected(2));
+        let messages = received(&player_1);
+        assert!(matches!(
+            messages.as_slice(),
+            [
+                RaceMessage::Progress { .. },
+                RaceMessage::Left { player: 2 },
+                RaceMessage::Winner {
+                    player: 1,
+                    actions_count: 12
+                }
+            ]
+        ));
+        assert!(relay.racing.is_empty());
+
+        // The next race starts with the waiting player and the one joining again
+        relay.handle(RelayEvent::Request(1, Ok(RaceRequest::Join)));
+        assert_eq!(started(&received(&player_1)), Some(vec![3, 1]));
+        assert_eq!(started(&received(&player_3)), Some(vec![3, 1]));
+
+        // A race left by all its players doesn't block the next one
+        relay.handle(RelayEvent::Disconnected(1));
+        relay.handle(RelayEvent::Disconnected(3));
+        assert!(relay.racing.is_empty());
+        let player_4 = connect(&mut relay, 4);
+        let player_5 = connect(&mut relay, 5);
+        assert_eq!(started(&received(&player_4)), Some(vec![4, 5]));
+        assert_eq!(started(&received(&player_5)), Some(vec![4, 5]));
+    }
+}
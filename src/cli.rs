@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::prelude::*;
+
+pub const USAGE: &str = "\
+Usage: taquinplus [OPTIONS]
+
+Any puzzle option starts a new puzzle right away, without showing the menu.
+Options that are not given use the settings of the menu.
+
+Puzzle options:
+  --size <WIDTH>x<HEIGHT>    Size of the grid, from 2x2 to 20x20
+  --seed <NUMBER>            Seed of the scramble, to play the same puzzle again
+  --image <PATH>             Image file of the puzzle
+  --actions <ACTIONS>        Comma separated actions used to scramble: slide,rotate,flip
+  --hole <random|corner>     Position of the hole
+  --mode <MODE>              classic, torus, hex, loopover, double-sided, expert, generated,
+                             or daily for the puzzle of the day, the same for everyone:
+                             --size, --actions and --hole are then ignored
+  --state <STATE>            Puzzle state written in the text format, like 3x3:1,2,3/4,.,5/7,8,6R
+                             --size, --seed, --actions, --hole and --mode are then ignored
+
+Other options:
+  --control <stdio|PORT>     Serve the JSON line control protocol, on the standard input and
+                             output or on a localhost TCP port
+  --race <HOST:PORT>         Join the online races of a taquin-relay server
+  --window <WIDTH>x<HEIGHT>  Size of the window, in pixels
+  --help                     Print this help
+";
+
+// Puzzle mode given with --mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CliMode {
+    Variant(PuzzleVariant),
+    // Classic puzzle seeded by the current date, the same for everyone on a given day
+    Daily,
+}
+
+// Size of the daily puzzle, which doesn't depend on the settings of the player
+const DAILY_SIZE: Coord = (4, 4);
+
+// Options given on the command line
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CliOptions {
+    pub size: Option<Coord>,
+    pub seed: Option<u64>,
+    pub image: Option<PathBuf>,
+    pub actions: Option<ScrambleActions>,
+    pub random_hole: Option<bool>,
+    pub mode: Option<CliMode>,
+    // Puzzle state in the text format, checked when parsed
+    pub state: Option<String>,
+    pub window: Option<(f32, f32)>,
+    pub control: Option<ControlEndpoint>,
+    // Address of the race relay
+    pub race: Option<String>,
+    pub help: bool,
+}
+impl CliOptions {
+    // Options of the program arguments, the program name excluded.
+    // Both "--option value" and "--option=value" are accepted.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = CliOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if name == "--help" || name == "-h" {
+                options.help = true;
+                continue;
+            }
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing value for {}", name))?;
+            match name.as_str() {
+                "--size" => {
+                    let (width, height) = parse_dimensions(&value)?;
+                    let range = MIN_PUZZLE_SIZE..=MAX_PUZZLE_SIZE;
+                    if !range.contains(&width) || !range.contains(&height) {
+                        return Err(format!(
+                            "Invalid size {}, sizes go from {}x{} to {}x{}",
+                            value,
+                            MIN_PUZZLE_SIZE,
+                            MIN_PUZZLE_SIZE,
+                            MAX_PUZZLE_SIZE,
+                            MAX_PUZZLE_SIZE
+                        ));
+                    }
+                    options.size = Some((height, width));
+                }
+                "--seed" => {
+                    options.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid seed {}", value))?,
+                    );
+                }
+                "--image" => options.image = Some(PathBuf::from(value)),
+                "--actions" => options.actions = Some(parse_actions(&value)?),
+                "--hole" => {
+                    options.random_hole = Some(match value.as_str() {
+                        "random" => true,
+                        "corner" => false,
+                        _ => return Err(format!("Invalid hole {}", value)),
+                    });
+                }
+                "--mode" => {
+                    options.mode = Some(match value.as_str() {
+                        "classic" => CliMode::Variant(PuzzleVariant::Classic),
+                        "torus" => CliMode::Variant(PuzzleVariant::Torus),
+                        "hex" => CliMode::Variant(PuzzleVariant::Hex),
+                        "loopover" => CliMode::Variant(PuzzleVariant::Loopover),
+                        "double-sided" => CliMode::Variant(PuzzleVariant::DoubleSided),
+                        "expert" => CliMode::Variant(PuzzleVariant::Expert),
+                        "generated" => CliMode::Variant(PuzzleVariant::Generated),
+                        "daily" => CliMode::Daily,
+                        _ => return Err(format!("Invalid mode {}", value)),
+                    });
+                }
+                "--state" => {
+                    Puzzle::from_text(&value, Handle::default())
+                        .map_err(|err| format!("Invalid state {}: {}", value, err))?;
+                    options.state = Some(value);
+                }
+                "--control" => options.control = Some(ControlEndpoint::parse(&value)?),
+                "--race" => options.race = Some(value),
+                "--window" => {
+                    let (width, height) = parse_dimensions(&value)?;
+                    options.window = Some((width as f32, height as f32));
+                }
+                _ => return Err(format!("Unknown option {}", name)),
+            }
+        }
+        Ok(options)
+    }
+    // True when a puzzle option is given, to start a puzzle without the menu
+    pub fn starts_puzzle(&self) -> bool {
+        self.size.is_some()
+            || self.seed.is_some()
+            || self.image.is_some()
+            || self.actions.is_some()
+            || self.random_hole.is_some()
+            || self.mode.is_some()
+            || self.state.is_some()
+    }
+    pub fn new_puzzle(&self, new_puzzle: &NewPuzzleSettings) -> Option<NewPuzzleOptions> {
+        if !self.starts_puzzle() {
+            return None;
+        }
+        if let Some(state) = &self.state {
+            let puzzle = Puzzle::from_text(state, Handle::default()).expect("Invalid state");
+            let variant = if puzzle.expert {
+                PuzzleVariant::Expert
+            } else if puzzle.double_sided {
+                PuzzleVariant::DoubleSided
+            } else if puzzle.shape == PuzzleShape::Hex {
+                PuzzleVariant::Hex
+            } else if puzzle.kind == PuzzleKind::Loopover {
+                PuzzleVariant::Loopover
+            } else if puzzle.wrapping {
+                PuzzleVariant::Torus
+            } else {
+                PuzzleVariant::Classic
+            };
+            let mut options = NewPuzzleOptions::new(variant, new_puzzle);
+            options.size = puzzle.size();
+            options.jigsaw = false;
+            options.image_path = self.image.clone();
+            options.state = Some(state.clone());
+            return Some(options);
+        }
+        let (variant, seed) = match self.mode {
+            Some(CliMode::Variant(variant)) => (variant, self.seed),
+            Some(CliMode::Daily) => {
+                // Only the date picks the puzzle, the scramble is the default one of its size
+                let mut options =
+                    NewPuzzleOptions::new(PuzzleVariant::Classic, &NewPuzzleSettings::default());
+                options.size = DAILY_SIZE;
+                options.jigsaw = false;
+                options.seed = Some(self.seed.unwrap_or_else(day_number));
+                options.image_path = self.image.clone();
+                return Some(options);
+            }
+            None => (PuzzleVariant::Classic, self.seed),
+        };
+        let mut options = NewPuzzleOptions::new(variant, new_puzzle);
+        options.size = self.size.unwrap_or(options.size);
+        options.seed = seed;
+        options.image_path = self.image.clone();
+        options.scramble = self.actions;
+        options.random_hole = self.random_hole;
+        Some(options)
+    }
+}
+
+// Dimensions written as <WIDTH>x<HEIGHT>
+fn parse_dimensions(value: &str) -> Result<(usize, usize), String> {
+    value
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .ok_or_else(|| format!("Invalid dimensions {}, expected <WIDTH>x<HEIGHT>", value))
+}
+
+fn parse_actions(value: &str) -> Result<ScrambleActions, String> {
+    let mut actions = ScrambleActions {
+        slide: false,
+        rotate: false,
+        flip: false,
+    };
+    for action in value.split(',') {
+        match action.trim() {
+            "slide" => actions.slide = true,
+            "rotate" => actions.rotate = true,
+            "flip" => actions.flip = true,
+            _ => return Err(format!("Invalid action {}", action)),
+        }
+    }
+    Ok(actions)
+}
+
+// Number of days since the Unix epoch, in UTC
+fn day_number() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() / 86400)
+}
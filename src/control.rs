@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+// Control protocol, for external programs like bots and automated tests.
+// Each line is a JSON object with a "type" field, requests are:
+//   {"type":"state"}
+//   {"type":"moves","moves":"U L a@2,3 +"}, moves being in the move notation
+//   {"type":"new_puzzle","size":"4x4","seed":1}, with the optional fields size, seed,
+//   mode, actions, hole and state of the command line options
+// Each request gets an "ok", "error" or "state" reply, and "solved" is sent to every
+// client when a puzzle is solved. The state and moves requests are about the focused
+// puzzle, a new puzzle replaces all the puzzles. Moves are only accepted while a puzzle
+// is being solved, and get the state reply once they are applied, the actions count
+// telling how many of them were done.
+
+// Where the control protocol is served, given with --control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlEndpoint {
+    // Standard input and output, for a program that starts the game
+    Stdio,
+    // TCP port on localhost, any number of clients can connect
+    Tcp(u16),
+}
+impl ControlEndpoint {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "stdio" => Ok(ControlEndpoint::Stdio),
+            port => port
+                .parse()
+                .map(ControlEndpoint::Tcp)
+                .map_err(|_| format!("Invalid control {}, expected stdio or a port", value)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlRequest {
+    State,
+    Moves {
+        moves: String,
+    },
+    NewPuzzle {
+        size: Option<String>,
+        seed: Option<u64>,
+        mode: Option<String>,
+        actions: Option<String>,
+        hole: Option<String>,
+        state: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlReply {
+    Ok,
+    Error {
+        message: String,
+    },
+    // Puzzle state in the text format, and moves of the player in the move notation
+    State {
+        state: String,
+        moves: String,
+        actions_count: usize,
+        solved: bool,
+    },
+    Solved {
+        moves: String,
+        actions_count: usize,
+    },
+}
+
+enum ControlMessage {
+    Connected(usize, Sender<String>),
+    Request(usize, Result<ControlRequest, String>),
+}
+
+// Requests received by the threads serving the protocol, and the clients to reply to
+#[derive(Resource)]
+pub struct ControlServer {
+    messages: Mutex<Receiver<ControlMessage>>,
+    clients: HashMap<usize, Sender<String>>,
+    // Clients waiting for the state of a puzzle, until their moves are applied
+    pending_moves: Vec<(usize, Entity)>,
+}
+impl ControlServer {
+    pub fn start(endpoint: ControlEndpoint) -> std::io::Result<Self> {
+        let (messages, receiver) = channel();
+        match endpoint {
+            ControlEndpoint::Stdio => {
+                let (replies, replies_receiver) = channel::<String>();
+                messages
+                    .send(ControlMessage::Connected(0, replies))
+                    .expect("Oops");
+                std::thread::spawn(move || {
+                    for reply in replies_receiver {
+                        let mut stdout = std::io::stdout().lock();
+                        if writeln!(stdout, "{}", reply)
+                            .and_then(|_| stdout.flush())
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+                std::thread::spawn(move || read_requests(0, std::io::stdin().lock(), messages));
+            }
+            ControlEndpoint::Tcp(port) => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+                std::thread::spawn(move || {
+                    for (client, stream) in listener.incoming().flatten().enumerate() {
+                        if let Err(err) = serve_client(client, stream, messages.clone()) {
+                            warn!("Control client {} failed: {}", client, err);
+                        }
+                    }
+                });
+            }
+        }
+        Ok(Self {
+            messages: Mutex::new(receiver),
+            clients: HashMap::new(),
+            pending_moves: vec![],
+        })
+    }
+    fn send(&mut self, client: usize, reply: &ControlReply) {
+        let reply = serde_json::to_string(reply).expect("Oops");
+        if let Some(sender) = self.clients.get(&client) {
+            if sender.send(reply).is_err() {
+                self.clients.remove(&client);
+            }
+        }
+    }
+    fn broadcast(&mut self, reply: &ControlReply) {
+        let reply = serde_json::to_string(reply).expect("Oops");
+        self.clients
+            .retain(|_, sender| sender.send(reply.clone()).is_ok());
+    }
+}
+
+fn serve_client(
+    client: usize,
+    stream: TcpStream,
+    messages: Sender<ControlMessage>,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let (replies, replies_receiver) = channel::<String>();
+    let _ = messages.send(ControlMessage::Connected(client, replies));
+    std::thread::spawn(move || {
+        for reply in replies_receiver {
+            if writeln!(writer, "{}", reply).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || read_requests(client, BufReader::new(stream), messages));
+    Ok(())
+}
+
+// Forwards each line of a client as a request, until the client or the game is gone
+fn read_requests(client: usize, reader: impl BufRead, messages: Sender<ControlMessage>) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = serde_json::from_str(&line).map_err(|err| err.to_string());
+        if messages
+            .send(ControlMessage::Request(client, request))
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+// Serves the protocol when requested on the command line
+pub fn start_control_server(
+    mut commands: Commands,
+    cli_options: Res<CliOptions>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let Some(endpoint) = cli_options.control else {
+        return;
+    };
+    match ControlServer::start(endpoint) {
+        Ok(server) => commands.insert_resource(server),
+        Err(err) => {
+            error!(
+                "Can't serve the control protocol on {:?}: {}",
+                endpoint, err
+            );
+            app_exit_events.send(AppExit::error());
+        }
+    }
+}
+
+fn state_reply(puzzle: &Puzzle) -> ControlReply {
+    ControlReply::State {
+        state: puzzle.to_text(),
+        moves: format_moves(&puzzle.history, puzzle.size()),
+        actions_count: puzzle.actions_count,
+        solved: puzzle.is_solved,
+    }
+}
+
+pub fn handle_control_requests(
+    mut commands: Commands,
+    mut server: ResMut<ControlServer>,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    // Requests are about the focused puzzle
+    puzzle: Query<(Entity, &Puzzle), With<FocusedPuzzle>>,
+    puzzles: Query<Entity, With<Puzzle>>,
+) {
+    let messages = server
+        .messages
+        .lock()
+        .expect("Oops")
+        .try_iter()
+        .collect::<Vec<_>>();
+    for message in messages {
+        let (client, request) = match message {
+            ControlMessage::Connected(client, sender) => {
+                server.clients.insert(client, sender);
+                continue;
+            }
+            ControlMessage::Request(client, request) => (client, request),
+        };
+        let reply = match request {
+            Err(message) => ControlReply::Error { message },
+            Ok(ControlRequest::State) => match puzzle.get_single() {
+                Ok((_, puzzle)) => state_reply(puzzle),
+                Err(_) => ControlReply::Error {
+                    message: "No puzzle".to_string(),
+                },
+            },
+            Ok(ControlRequest::Moves { .. }) if state.get() != &GameState::PuzzleSolving => {
+                ControlReply::Error {
+                    message: "Moves are only accepted while solving a puzzle".to_string(),
+                }
+            }
+            Ok(ControlRequest::Moves { moves }) => match puzzle.get_single() {
+                Ok((entity, puzzle)) => match parse_moves(&moves, puzzle.size()) {
+                    Ok(actions) => {
+                        puzzle_action_events.send_batch(actions.into_iter().map(|action| {
+                            PuzzleActionEvent {
+                                puzzle: entity,
+                                action,
+                            }
+                        }));
+                        // The state is sent back once the actions are handled
+                        server.pending_moves.push((client, entity));
+                        continue;
+                    }
+                    Err(message) => ControlReply::Error { message },
+                },
+                Err(_) => ControlReply::Error {
+                    message: "No puzzle".to_string(),
+                },
+            },
+            Ok(ControlRequest::NewPuzzle {
+                size,
+                seed,
+                mode,
+                actions,
+                hole,
+                state,
+            }) => {
+                // Fields are the options of the command line
+                let args = [
+                    ("--size", size),
+                    ("--seed", seed.map(|seed| seed.to_string())),
+                    ("--mode", mode),
+                    ("--actions", actions),
+                    ("--hole", hole),
+                    ("--state", state),
+                ]
+                .into_iter()
+                .filter_map(|(name, value)| Some(format!("{}={}", name, value?)));
+                match CliOptions::parse(args) {
+                    Ok(cli_options) => {
+                        let options =
+                            cli_options
+                                .new_puzzle(&settings.new_puzzle)
+                                .unwrap_or_else(|| {
+                                    NewPuzzleOptions::new(
+                                        PuzzleVariant::Classic,
+                                        &settings.new_puzzle,
+                                    )
+                                });
+                        for entity in puzzles.iter() {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                        spawn_new_puzzle(&mut commands, &mut images, &asset_server, &options);
+                        next_state.set(GameState::PuzzleSolving);
+                        ControlReply::Ok
+                    }
+                    Err(message) => ControlReply::Error { message },
+                }
+            }
+        };
+        server.send(client, &reply);
+    }
+}
+
+// Replies to the moves requests with the state of their puzzle, once the moves are applied
+pub fn send_control_move_replies(mut server: ResMut<ControlServer>, puzzle: Query<&Puzzle>) {
+    for (client, entity) in std::mem::take(&mut server.pending_moves) {
+        let reply = match puzzle.get(entity) {
+            Ok(puzzle) => state_reply(puzzle),
+            Err(_) => ControlReply::Error {
+                message: "The puzzle was replaced".to_string(),
+            },
+        };
+        server.send(client, &reply);
+    }
+}
+
+pub fn send_control_solved_event(
+    mut server: ResMut<ControlServer>,
+    mut solved_events: EventReader<PuzzleSolvedEvent>,
+    puzzle: Query<&Puzzle>,
+) {
+    for event in solved_events.read() {
+        if let Ok(puzzle) = puzzle.get(event.puzzle) {
+            server.broadcast(&ControlReply::Solved {
+                moves: format_moves(&puzzle.history, puzzle.size()),
+                actions_count: event.actions_count,
+            });
+        }
+    }
+}
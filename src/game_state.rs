@@ -9,6 +9,29 @@ pub enum GameState {
     PuzzleSolved,
 }
 
-pub fn transition_setup_to_menu(mut state: ResMut<NextState<GameState>>) {
-    state.set(GameState::Menu);
+// Page of the menu shown in the Menu state
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, SubStates)]
+#[source(GameState = GameState::Menu)]
+pub enum MenuPage {
+    #[default]
+    Main,
+    Settings,
+}
+
+// Starts the puzzle given on the command line, or shows the menu
+pub fn transition_from_setup(
+    mut commands: Commands,
+    mut state: ResMut<NextState<GameState>>,
+    cli_options: Res<CliOptions>,
+    settings: Res<Settings>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    match cli_options.new_puzzle(&settings.new_puzzle) {
+        Some(options) => {
+            spawn_new_puzzle(&mut commands, &mut images, &asset_server, &options);
+            state.set(GameState::PuzzleSolving);
+        }
+        None => state.set(GameState::Menu),
+    }
 }
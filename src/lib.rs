@@ -0,0 +1,14 @@
+mod audio;
+mod cli;
+mod control;
+mod game_state;
+mod locale;
+mod plugin;
+pub mod prelude;
+mod puzzle;
+mod race;
+mod settings;
+mod theme;
+mod tile;
+mod ui;
+mod versus;
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font as _, FontRef};
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+// Every language file, the first one being the default language.
+// Keys missing from a language use the text of the default language.
+const LANGUAGE_FILES: [&str; 2] = [
+    include_str!("../assets/locales/en.ron"),
+    include_str!("../assets/locales/fr.ron"),
+];
+const UI_FONT: &str = "fonts/FiraMono-Medium.ttf";
+
+#[derive(Debug, Deserialize)]
+struct Language {
+    // ISO 639-1 code
+    code: String,
+    // Name of the language, in that language
+    name: String,
+    // Asset path of a font used for the texts that the UI font can't render
+    fallback_font: Option<String>,
+    // Texts by key, where {0}, {1}... are replaced by arguments
+    strings: HashMap<String, String>,
+}
+
+// Texts of the UI in the selected language, with the fonts able to render them
+#[derive(Resource)]
+pub struct Localization {
+    languages: Vec<Language>,
+    current: usize,
+    font: Handle<Font>,
+    fallback_font: Option<Handle<Font>>,
+    // Embedded copy of the UI font, to check which characters it supports
+    font_glyphs: FontRef<'static>,
+}
+impl FromWorld for Localization {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let languages = LANGUAGE_FILES
+            .iter()
+            .map(|ron| ron::from_str(ron).expect("Invalid built-in language file"))
+            .collect();
+        let mut localization = Self {
+            languages,
+            current: 0,
+            font: asset_server.load(UI_FONT),
+            fallback_font: None,
+            font_glyphs: FontRef::try_from_slice(FIRA_MONO).expect("Invalid embedded font"),
+        };
+        localization.select(0, asset_server);
+        localization
+    }
+}
+impl Localization {
+    pub fn language_code(&self) -> &str {
+        &self.languages[self.current].code
+    }
+    pub fn language_name(&self) -> &str {
+        &self.languages[self.current].name
+    }
+    // Code of the language after the current one, or before it for a negative delta
+    pub fn cycle(&self, delta: isize) -> String {
+        let next = (self.current as isize + delta).rem_euclid(self.languages.len() as isize);
+        self.languages[next as usize].code.clone()
+    }
+    // Selects the language with the given code, unknown codes are ignored
+    pub fn select_code(&mut self, code: &str, asset_server: &AssetServer) {
+        match self
+            .languages
+            .iter()
+            .position(|language| language.code == code)
+        {
+            Some(index) => self.select(index, asset_server),
+            None => warn!("Unknown language {}", code),
+        }
+    }
+    fn select(&mut self, index: usize, asset_server: &AssetServer) {
+        self.current = index;
+        self.fallback_font = self.languages[index]
+            .fallback_font
+            .as_ref()
+            .map(|path| asset_server.load(path.clone()));
+    }
+    // Text of the key in the current language, if any language has it
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.languages[self.current]
+            .strings
+            .get(key)
+            .or_else(|| self.languages[0].strings.get(key))
+            .map(String::as_str)
+    }
+    // Text of the key, with {0}, {1}... replaced by the arguments.
+    // A missing key is shown as is, to be easy to spot.
+    pub fn text(&self, key: &str, args: &[&dyn std::fmt::Display]) -> String {
+        let Some(text) = self.get(key) else {
+            warn!("Missing text for {}", key);
+            return key.to_string();
+        };
+        args.iter()
+            .enumerate()
+            .fold(text.to_string(), |text, (i, arg)| {
+                text.replace(&format!("{{{}}}", i), &arg.to_string())
+            })
+    }
+    // UI font if it can render all the characters of the text, else the fallback font
+    // of the current language
+    pub fn font_for(&self, text: &str) -> Handle<Font> {
+        let supported = text
+            .chars()
+            .all(|c| c.is_whitespace() || self.font_glyphs.glyph_id(c).0 != 0);
+        match (&self.fallback_font, supported) {
+            (Some(fallback_font), false) => fallback_font.clone(),
+            _ => self.font.clone(),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::render::view::{Layer, RenderLayers};
+use bevy::window::WindowResized;
+use bevy_mod_picking::picking_core::CorePlugin as PickingCorePlugin;
+
+use crate::prelude::*;
+
+// The puzzle game, for taquinplus or to embed it in another app.
+// Settings and command line options can be inserted by the app before adding the plugin,
+// for example CliOptions::parse(["--size=3x3"]) to start a puzzle right away.
+// By default the plugin leaves the window, the settings file, the UI, the control
+// protocol and online races to the app, taquinplus uses TaquinPlugin::standalone()
+// to get them all.
+// Solved puzzles are announced with PuzzleSolvedEvent, and puzzles can be driven by
+// sending PuzzleAction events.
+pub struct TaquinPlugin {
+    // Spawns the camera showing the puzzles. Otherwise the app must add TaquinCamera to
+    // its own camera, along with GaussianBlurSettings to get the menu blur.
+    pub spawn_camera: bool,
+    // Render layer of the puzzle entities, to show them only with the camera of the app
+    pub render_layer: Option<Layer>,
+    // Applies the window mode setting to the primary window
+    pub manage_window: bool,
+    // Saves changed settings, to the settings file or the browser local storage
+    pub save_settings: bool,
+    // Spawns the header and the menu, from which versus races are started. Otherwise
+    // the app starts puzzles itself, and the menu state only blurs the puzzle.
+    pub show_menu: bool,
+    // Serves the control protocol on the endpoint of the --control option
+    pub remote_control: bool,
+    // Joins the online races of the relay of the --race option
+    pub online_races: bool,
+}
+impl Default for TaquinPlugin {
+    fn default() -> Self {
+        Self {
+            spawn_camera: true,
+            render_layer: None,
+            manage_window: false,
+            save_settings: false,
+            show_menu: false,
+            remote_control: false,
+            online_races: false,
+        }
+    }
+}
+impl TaquinPlugin {
+    // The game as its own app, which owns its window
+    pub fn standalone() -> Self {
+        Self {
+            manage_window: true,
+            save_settings: true,
+            show_menu: true,
+            remote_control: true,
+            online_races: true,
+            ..default()
+        }
+    }
+}
+
+// Camera showing the puzzles, it is blurred behind the menu
+#[derive(Component)]
+pub struct TaquinCamera;
+
+#[derive(Resource)]
+struct TaquinRenderLayers(RenderLayers);
+
+impl Plugin for TaquinPlugin {
+    fn build(&self, app: &mut App) {
+        // Settings are only read from the file that they are saved to
+        if !app.world().contains_resource::<Settings>() {
+            app.insert_resource(if self.save_settings {
+                Settings::load()
+            } else {
+                Settings::default()
+            });
+        }
+        // Plugins the game depends on may already be used by the app
+        if !app.is_plugin_added::<TweeningPlugin>() {
+            app.add_plugins(TweeningPlugin);
+        }
+        if !app.is_plugin_added::<GaussianBlurPlugin>() {
+            app.add_plugins(GaussianBlurPlugin);
+        }
+        if !app.is_plugin_added::<PickingCorePlugin>() {
+            app.add_plugins(
+                DefaultPickingPlugins
+                    .build()
+                    .disable::<DebugPickingPlugin>()
+                    .disable::<DefaultHighlightingPlugin>(),
+            );
+        }
+        if !app.is_plugin_added::<OutlinePlugin>() {
+            app.add_plugins(OutlinePlugin);
+        }
+        if let Some(layer) = self.render_layer {
+            app.insert_resource(TaquinRenderLayers(RenderLayers::layer(layer)))
+                .add_systems(Update, apply_render_layers);
+        }
+        if self.spawn_camera {
+            app.add_systems(Startup, spawn_camera);
+        }
+        if self.manage_window {
+            app.add_systems(Update, apply_window_settings);
+        }
+        if self.save_settings {
+            app.add_systems(Update, save_changed_settings);
+        }
+        if self.show_menu {
+            app.add_systems(Update, setup_ui_header.run_if(in_state(GameState::Setup)))
+                .add_systems(Update, update_ui_header)
+                .add_systems(OnEnter(MenuPage::Main), setup_menu)
+                .add_systems(OnExit(MenuPage::Main), despawn_menu)
+                .add_systems(OnEnter(MenuPage::Settings), setup_menu)
+                .add_systems(OnExit(MenuPage::Settings), despawn_menu)
+                .add_systems(
+                    Update,
+                    (
+                        menu_active_update,
+                        menu_settings_update,
+                        menu_interaction,
+                        menu_event_handler,
+                    )
+                        .run_if(in_state(GameState::Menu)),
+                )
+                // Versus races have their own controls, the keys of both players being taken
+                .add_systems(
+                    Update,
+                    (
+                        versus_input.before(handle_puzzle_action_events),
+                        tick_versus_race,
+                    )
+                        .run_if(in_state(GameState::PuzzleSolving))
+                        .run_if(resource_exists::<VersusRace>),
+                )
+                .add_systems(
+                    Update,
+                    (
+                        finish_versus_race.after(handle_puzzle_action_events),
+                        end_versus_race,
+                        update_versus_scores,
+                    )
+                        .run_if(resource_exists::<VersusRace>),
+                )
+                .add_systems(
+                    Update,
+                    setup_versus_scores.run_if(resource_added::<VersusRace>),
+                )
+                .add_systems(
+                    OnEnter(GameState::PuzzleSolved),
+                    show_versus_results.run_if(resource_exists::<VersusRace>),
+                )
+                .add_systems(OnExit(GameState::PuzzleSolved), despawn_versus_results);
+        }
+        if self.remote_control {
+            app.add_systems(Startup, start_control_server).add_systems(
+                Update,
+                (
+                    handle_control_requests.before(handle_puzzle_action_events),
+                    send_control_move_replies.after(handle_puzzle_action_events),
+                    send_control_solved_event.after(handle_puzzle_action_events),
+                )
+                    .run_if(resource_exists::<ControlServer>),
+            );
+        }
+        if self.online_races {
+            app.add_systems(Startup, start_race_client)
+                .add_systems(
+                    Update,
+                    (
+                        handle_race_messages,
+                        send_race_progress.after(handle_puzzle_action_events),
+                    )
+                        .run_if(resource_exists::<RaceClient>),
+                )
+                // The progress bars are removed with the client
+                .add_systems(
+                    Update,
+                    update_race_progress_bars.run_if(
+                        resource_exists::<RaceClient>.or_else(resource_removed::<RaceClient>()),
+                    ),
+                );
+        }
+        app.init_state::<GameState>()
+            .add_sub_state::<MenuPage>()
+            .init_resource::<CliOptions>()
+            .init_resource::<Localization>()
+            .init_resource::<Theme>()
+            .init_resource::<Themes>()
+            .init_resource::<ActionReplay>()
+            .add_event::<PuzzleAction>()
+            .add_event::<PuzzleActionEvent>()
+            .add_event::<PuzzleSolvedEvent>()
+            .add_event::<MenuEntry>()
+            .add_event::<SoundEffect>()
+            .add_systems(
+                Update,
+                transition_from_setup.run_if(in_state(GameState::Setup)),
+            )
+            .add_systems(
+                Update,
+                (keep_puzzle_focus, handle_puzzle_action_events).chain(),
+            )
+            .add_systems(Update, show_full_puzzle.after(handle_puzzle_action_events))
+            .add_systems(Update, compose_double_sided_images)
+            .add_systems(Update, puzzle_resize)
+            .add_systems(Update, asset_animator_system::<Mesh>)
+            .add_systems(Update, component_animator_system::<GaussianBlurSettings>)
+            .add_systems(Update, tile_animation)
+            .add_systems(Update, apply_settings)
+            .add_systems(Update, apply_puzzle_settings)
+            .add_systems(Update, apply_theme)
+            .add_systems(Update, show_error_badges)
+            .add_systems(Update, (play_sound_effects, update_background_music))
+            .add_systems(
+                Update,
+                (
+                    puzzle_solving_interaction.run_if(not(resource_exists::<VersusRace>)),
+                    replay_actions.before(handle_puzzle_action_events),
+                )
+                    .run_if(in_state(GameState::PuzzleSolving)),
+            )
+            // Moves can still be copied once the puzzle is solved
+            .add_systems(
+                Update,
+                copy_paste_moves
+                    .run_if(
+                        in_state(GameState::PuzzleSolving)
+                            .or_else(in_state(GameState::PuzzleSolved)),
+                    )
+                    .run_if(not(resource_exists::<VersusRace>)),
+            )
+            .add_systems(OnEnter(GameState::PuzzleSolving), spawn_puzzle_entities)
+            .add_systems(OnEnter(GameState::Menu), puzzle_blur)
+            .add_systems(OnExit(GameState::Menu), puzzle_deblur)
+            .add_systems(
+                Update,
+                puzzle_solved_interaction.run_if(in_state(GameState::PuzzleSolved)),
+            );
+    }
+}
+
+fn spawn_camera(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    cli_options: Res<CliOptions>,
+    render_layers: Option<Res<TaquinRenderLayers>>,
+) {
+    let projection = OrthographicProjection {
+        far: 1000.,
+        near: -1000.,
+        ..default()
+    };
+    let mut camera = commands.spawn((
+        Camera3dBundle {
+            projection: Projection::Orthographic(projection),
+            transform: Transform::from_xyz(0.0, 0., 20.).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        // The menu is blurred, a puzzle started from the command line is not
+        if cli_options.starts_puzzle() {
+            GaussianBlurSettings::NO_BLUR
+        } else {
+            settings.blur()
+        },
+        TaquinCamera,
+    ));
+    if let Some(render_layers) = render_layers {
+        camera.insert(render_layers.0.clone());
+    }
+
+    commands.insert_resource(AmbientLight {
+        brightness: 3.0,
+        ..default()
+    });
+}
+
+// Lays out the puzzles side by side in the viewport of the camera, below the header
+fn puzzle_resize(
+    camera: Query<&Camera, With<TaquinCamera>>,
+    mut puzzles: Query<(Entity, &mut Transform, &Puzzle, Option<&PuzzleSlot>)>,
+    added_puzzle: Query<(), (With<Puzzle>, Added<GlobalTransform>)>,
+    mut removed_puzzles: RemovedComponents<Puzzle>,
+    mut resize_events: EventReader<WindowResized>,
+) {
+    if resize_events.is_empty() && added_puzzle.is_empty() && removed_puzzles.is_empty() {
+        return;
+    }
+    resize_events.clear();
+    removed_puzzles.clear();
+    let Some(viewport) = camera
+        .get_single()
+        .ok()
+        .and_then(|camera| camera.logical_viewport_size())
+    else {
+        return;
+    };
+    let mut puzzles = puzzles.iter_mut().collect::<Vec<_>>();
+    puzzles.sort_by_key(|(entity, _, _, slot)| puzzle_order(*entity, *slot));
+    let height = viewport.y - UI_HEADER_PX;
+    let width = viewport.x / puzzles.len().max(1) as f32;
+    for (i, (_, mut puzzle_transform, puzzle, _)) in puzzles.into_iter().enumerate() {
+        // Rectangular grids don't use the full unit square
+        let extent = puzzle_extent(puzzle.size(), puzzle.shape);
+        let min = 0.95 * (height / extent.y).min(width / extent.x);
+        puzzle_transform.scale = Vec3::new(min, min, 1.);
+        puzzle_transform.translation.x = (i as f32 + 0.5) * width - viewport.x / 2.;
+        puzzle_transform.translation.y = -UI_HEADER_PX / 2.;
+    }
+}
+
+// Puts the puzzles and all their entities on the render layer of the plugin
+fn apply_render_layers(
+    mut commands: Commands,
+    render_layers: Res<TaquinRenderLayers>,
+    puzzles: Query<Entity, With<Puzzle>>,
+    children: Query<&Children>,
+    without_layers: Query<(), Without<RenderLayers>>,
+) {
+    for puzzle in puzzles.iter() {
+        for entity in std::iter::once(puzzle).chain(children.iter_descendants(puzzle)) {
+            if without_layers.contains(entity) {
+                commands.entity(entity).insert(render_layers.0.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use crate::prelude::*;
+
+// Diameter of a badge, relative to its tile
+const ERROR_BADGE_SCALE: f32 = 0.3;
+
+// Kind of error shown by a badge. Each kind has its own icon and corner,
+// so that errors can be told apart without relying on outline colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorBadgeKind {
+    Misplaced,
+    Misoriented,
+}
+impl ErrorBadgeKind {
+    const ALL: [ErrorBadgeKind; 2] = [ErrorBadgeKind::Misplaced, ErrorBadgeKind::Misoriented];
+    fn icon(&self) -> &'static str {
+        match self {
+            ErrorBadgeKind::Misplaced => "ActionTipIconMoveRight.png",
+            ErrorBadgeKind::Misoriented => "ActionTipIconRotateCW.png",
+        }
+    }
+    // Top left corner for misplaced, top right corner for misoriented, in tile local coordinates
+    fn translation(&self, shape: PuzzleShape) -> Vec3 {
+        let corner = match shape {
+            PuzzleShape::Square => 0.3,
+            // Kept inside the hexagon
+            PuzzleShape::Hex => 0.22,
+        };
+        let x = match self {
+            ErrorBadgeKind::Misplaced => -corner,
+            ErrorBadgeKind::Misoriented => corner,
+        };
+        // Above the top face of hexagonal tiles
+        Vec3::new(x, corner, 0.6)
+    }
+}
+
+// Badge spawned as a child of each tile, only visible when its error is shown
+#[derive(Component)]
+pub struct ErrorBadge(pub ErrorBadgeKind);
+
+// Meshes and materials shared by all the error badges of a puzzle
+pub struct ErrorBadgeAssets {
+    disc_mesh: Handle<Mesh>,
+    disc_material: Handle<StandardMaterial>,
+    icon_mesh: Handle<Mesh>,
+    icon_materials: HashMap<ErrorBadgeKind, Handle<StandardMaterial>>,
+}
+impl ErrorBadgeAssets {
+    pub fn new(
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        asset_server: &AssetServer,
+    ) -> Self {
+        Self {
+            disc_mesh: meshes.add(Mesh::from(Circle::new(0.5))),
+            disc_material: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.0, 0.0, 0.0, 0.75),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            icon_mesh: meshes.add(Mesh::from(Rectangle::new(0.8, 0.8))),
+            icon_materials: ErrorBadgeKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    let material = materials.add(StandardMaterial {
+                        base_color_texture: Some(asset_server.load(kind.icon())),
+                        alpha_mode: AlphaMode::Blend,
+                        unlit: true,
+                        ..default()
+                    });
+                    (kind, material)
+                })
+                .collect(),
+        }
+    }
+    pub fn spawn(&self, tile: &mut ChildBuilder, shape: PuzzleShape) {
+        for kind in ErrorBadgeKind::ALL {
+            tile.spawn((
+                PbrBundle {
+                    mesh: self.disc_mesh.clone(),
+                    material: self.disc_material.clone(),
+                    transform: Transform::from_translation(kind.translation(shape))
+                        .with_scale(Vec3::splat(ERROR_BADGE_SCALE)),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                ErrorBadge(kind),
+                Pickable::IGNORE,
+                Name::new(format!("ErrorBadge_{:?}", kind)),
+            ))
+            .with_children(|badge| {
+                badge.spawn((
+                    PbrBundle {
+                        mesh: self.icon_mesh.clone(),
+                        material: self.icon_materials[&kind].clone(),
+                        // Slightly on top of the disc to avoid clipping
+                        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.1)),
+                        ..default()
+                    },
+                    Pickable::IGNORE,
+                ));
+            });
+        }
+    }
+}
+
+// Badges follow the errors shown on Space, when enabled in the settings
+pub fn show_error_badges(
+    puzzle: Query<Ref<Puzzle>>,
+    settings: Res<Settings>,
+    mut badges: Query<(&ErrorBadge, &Parent, &mut Visibility)>,
+) {
+    // Errors of the tiles of changed puzzles, and whether they are shown
+    let tile_errors = puzzle
+        .iter()
+        .filter(|puzzle| puzzle.is_changed() || settings.is_changed())
+        .flat_map(|puzzle| {
+            let show_errors = settings.error_badges && puzzle.show_errors && !puzzle.is_solved;
+            puzzle
+                .tiles
+                .indexed_iter()
+                .filter_map(|(index, tile)| {
+                    let tile = tile.as_ref()?;
+                    Some((tile.entity?, (show_errors, puzzle.tile_errors(index, tile))))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<HashMap<_, _>>();
+    if tile_errors.is_empty() {
+        return;
+    }
+    for (badge, tile, mut visibility) in badges.iter_mut() {
+        let Some((show_errors, (misplaced, misoriented))) = tile_errors.get(&tile.get()).copied()
+        else {
+            continue;
+        };
+        let has_error = match badge.0 {
+            ErrorBadgeKind::Misplaced => misplaced,
+            ErrorBadgeKind::Misoriented => misoriented,
+        };
+        *visibility = if show_errors && has_error {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,75 @@
+use crate::prelude::*;
+
+// The different games that can be played on a puzzle grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PuzzleKind {
+    // Classic sliding puzzle, tiles are moved into the hole
+    #[default]
+    Taquin,
+    // No hole, a full row or column is cyclically shifted
+    Loopover,
+}
+
+// Shape of the tiles, and so of the grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PuzzleShape {
+    #[default]
+    Square,
+    // Pointy-top hexagons, where odd rows are shifted half a tile to the right
+    Hex,
+}
+impl PuzzleShape {
+    // Number of rotation steps to make a full turn
+    pub fn rotation_order(&self) -> u8 {
+        match self {
+            PuzzleShape::Square => 4,
+            PuzzleShape::Hex => 6,
+        }
+    }
+}
+
+// Description of the grid and rules of a new puzzle
+#[derive(Debug, Clone)]
+pub struct PuzzleConfig {
+    pub width: usize,
+    pub height: usize,
+    pub kind: PuzzleKind,
+    // Hexagonal grids only support Taquin puzzles without wrapping
+    pub shape: PuzzleShape,
+    // When false, holes are placed from the bottom right corner
+    pub random_hole: bool,
+    // When true the grid wraps around, tiles on an edge can move to the opposite edge
+    pub wrapping: bool,
+    // Number of holes, ignored for Loopover
+    pub n_holes: usize,
+    // Number of fixed tiles that can't be moved, ignored for Loopover
+    pub n_walls: usize,
+    // When true each tile has a back image, shown when the tile is flipped
+    pub double_sided: bool,
+    // For double-sided puzzles, requires to complete the back image as well as the front one
+    pub expert: bool,
+    // Square puzzles only, tiles have interlocking tabs and blanks like a jigsaw puzzle
+    pub jigsaw: bool,
+}
+impl Default for PuzzleConfig {
+    fn default() -> Self {
+        Self {
+            width: 3,
+            height: 3,
+            kind: PuzzleKind::Taquin,
+            shape: PuzzleShape::Square,
+            random_hole: false,
+            wrapping: false,
+            n_holes: 1,
+            n_walls: 0,
+            double_sided: false,
+            expert: false,
+            jigsaw: false,
+        }
+    }
+}
+impl PuzzleConfig {
+    pub fn size(&self) -> Coord {
+        (self.height, self.width)
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::JoinHandle,
+};
+
+use crate::prelude::*;
+
+// Reward of every step, to favor short solutions
+const STEP_REWARD: f32 = -0.01;
+const SOLVED_REWARD: f32 = 1.0;
+const DEFAULT_MAX_STEPS: usize = 1000;
+// Batches are stepped on several threads, each with at least this number of puzzles
+const MIN_ENVS_PER_THREAD: usize = 64;
+
+// Values of each cell in an observation
+pub const OBSERVATION_CELL_LEN: usize = 5;
+
+// Flat encoding of a puzzle for agents. Cells are in reading order, top row first,
+// each with OBSERVATION_CELL_LEN values: the number of its tile in reading order of the
+// solved puzzle (0 for a hole), 1 for a wall, the clock-wise rotation steps,
+// 1 for a flip on the local X axis and 1 for a flip on the local Y axis.
+// They are followed by the active row and column, in the same order, and the active hole.
+pub type Observation = Vec<u16>;
+
+pub fn observe(puzzle: &Puzzle) -> Observation {
+    let (height, width) = puzzle.size();
+    let mut observation = Vec::with_capacity(height * width * OBSERVATION_CELL_LEN + 3);
+    for row in (0..height).rev() {
+        for column in 0..width {
+            match &puzzle.tiles[(row, column)] {
+                None => observation.extend([0; OBSERVATION_CELL_LEN]),
+                Some(tile) => {
+                    let (flipped_x, flipped_y) = tile.flips();
+                    let number = (height - 1 - tile.position.0) * width + tile.position.1 + 1;
+                    observation.extend([
+                        number as u16,
+                        tile.fixed as u16,
+                        tile.rotation.steps() as u16,
+                        flipped_x as u16,
+                        flipped_y as u16,
+                    ]);
+                }
+            }
+        }
+    }
+    observation.extend([
+        (height - 1 - puzzle.active.0) as u16,
+        puzzle.active.1 as u16,
+        puzzle.active_hole as u16,
+    ]);
+    observation
+}
+
+// Actions an agent can take on a puzzle, by index.
+// They only depend on the kind and shape of the puzzle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionSpace {
+    actions: Vec<PuzzleAction>,
+}
+impl ActionSpace {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        use PuzzleAction::*;
+        let mut actions = match puzzle.kind {
+            PuzzleKind::Taquin => puzzle.move_candidates().to_vec(),
+            PuzzleKind::Loopover => {
+                vec![ShiftRowLeft, ShiftRowRight, ShiftColumnUp, ShiftColumnDown]
+            }
+        };
+        actions.extend([
+            MoveActiveLeft,
+            MoveActiveRight,
+            MoveActiveUp,
+            MoveActiveDown,
+        ]);
+        if puzzle.holes.len() > 1 {
+            actions.push(NextHole);
+        }
+        actions.extend([ActiveFlipX, ActiveFlipY, ActiveRotateCW, ActiveRotateCCW]);
+        Self { actions }
+    }
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+    pub fn get(&self, index: usize) -> Option<PuzzleAction> {
+        self.actions.get(index).copied()
+    }
+    pub fn index_of(&self, action: PuzzleAction) -> Option<usize> {
+        self.actions
+            .iter()
+            .position(|candidate| *candidate == action)
+    }
+    pub fn actions(&self) -> &[PuzzleAction] {
+        &self.actions
+    }
+}
+
+// Result of a step of an environment
+#[derive(Debug, Clone)]
+pub struct EnvStep {
+    pub observation: Observation,
+    // Change of progress, with a small penalty for each step and a bonus once solved
+    pub reward: f32,
+    // True once solved or after the maximum number of steps
+    pub done: bool,
+}
+
+// Gym-style environment on a headless puzzle, for reinforcement learning
+pub struct PuzzleEnv {
+    puzzle: Puzzle,
+    action_space: ActionSpace,
+    steps: usize,
+    pub max_steps: usize,
+}
+impl PuzzleEnv {
+    pub fn new(options: &NewPuzzleOptions, seed: u64) -> Self {
+        let puzzle = new_env_puzzle(options, seed);
+        Self {
+            action_space: ActionSpace::new(&puzzle),
+            puzzle,
+            steps: 0,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+    // Starts a new scrambled puzzle, the same one for the same options and seed
+    pub fn reset(&mut self, options: &NewPuzzleOptions, seed: u64) -> Observation {
+        self.puzzle = new_env_puzzle(options, seed);
+        self.action_space = ActionSpace::new(&self.puzzle);
+        self.steps = 0;
+        self.observation()
+    }
+    // Applies the action of the given index in the action space.
+    // Actions that can't be applied, like moving a wall, only cost a step.
+    pub fn step(&mut self, action: usize) -> Result<EnvStep, String> {
+        let action = self.action(action)?;
+        Ok(self.step_action(action))
+    }
+    fn action(&self, index: usize) -> Result<PuzzleAction, String> {
+        self.action_space.get(index).ok_or_else(|| {
+            format!(
+                "Invalid action index {}, the action space has {} actions",
+                index,
+                self.action_space.len()
+            )
+        })
+    }
+    fn step_action(&mut self, action: PuzzleAction) -> EnvStep {
+        if self.is_done() {
+            return EnvStep {
+                observation: self.observation(),
+                reward: 0.,
+                done: true,
+            };
+        }
+        let progress = self.puzzle.progress();
+        self.puzzle.apply_action(action);
+        self.steps += 1;
+        let mut reward = self.puzzle.progress() - progress + STEP_REWARD;
+        if self.puzzle.is_solved {
+            reward += SOLVED_REWARD;
+        }
+        EnvStep {
+            observation: self.observation(),
+            reward,
+            done: self.is_done(),
+        }
+    }
+    pub fn is_done(&self) -> bool {
+        self.puzzle.is_solved || self.steps >= self.max_steps
+    }
+    pub fn observation(&self) -> Observation {
+        observe(&self.puzzle)
+    }
+    pub fn action_space(&self) -> &ActionSpace {
+        &self.action_space
+    }
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+}
+
+fn new_env_puzzle(options: &NewPuzzleOptions, seed: u64) -> Puzzle {
+    let mut options = options.clone();
+    options.seed = Some(seed);
+    options.create_puzzle(Handle::default())
+}
+
+fn step_envs(envs: &mut [PuzzleEnv], actions: &[PuzzleAction]) -> Vec<EnvStep> {
+    envs.iter_mut()
+        .zip(actions)
+        .map(|(env, action)| env.step_action(*action))
+        .collect()
+}
+
+// Chunk of environments sent to a worker with their actions, and sent back once stepped
+type EnvJob = (Vec<PuzzleEnv>, Vec<PuzzleAction>);
+type EnvJobResult = (Vec<PuzzleEnv>, Vec<EnvStep>);
+
+// Threads kept for the whole life of a batch, each stepping the chunks it receives
+struct EnvWorkers {
+    jobs: Vec<Sender<EnvJob>>,
+    results: Vec<Receiver<EnvJobResult>>,
+    threads: Vec<JoinHandle<()>>,
+}
+impl EnvWorkers {
+    fn new(n_threads: usize) -> Self {
+        let mut workers = Self {
+            jobs: vec![],
+            results: vec![],
+            threads: vec![],
+        };
+        for _ in 0..n_threads {
+            let (job_sender, jobs) = channel::<EnvJob>();
+            let (results, result_receiver) = channel();
+            workers.threads.push(std::thread::spawn(move || {
+                for (mut envs, actions) in jobs {
+                    let steps = step_envs(&mut envs, &actions);
+                    if results.send((envs, steps)).is_err() {
+                        break;
+                    }
+                }
+            }));
+            workers.jobs.push(job_sender);
+            workers.results.push(result_receiver);
+        }
+        workers
+    }
+}
+impl Drop for EnvWorkers {
+    fn drop(&mut self) {
+        // Closing the job channels ends the worker loops
+        self.jobs.clear();
+        for thread in self.threads.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+// Environments stepped together, on all the CPU cores for large batches
+pub struct BatchEnv {
+    pub envs: Vec<PuzzleEnv>,
+    workers: EnvWorkers,
+}
+impl BatchEnv {
+    // One environment for each seed
+    pub fn new(options: &NewPuzzleOptions, seeds: impl IntoIterator<Item = u64>) -> Self {
+        let envs = seeds
+            .into_iter()
+            .map(|seed| PuzzleEnv::new(options, seed))
+            .collect::<Vec<_>>();
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let n_threads = threads.min(envs.len().div_ceil(MIN_ENVS_PER_THREAD));
+        Self {
+            envs,
+            // A single chunk is stepped on the calling thread
+            workers: EnvWorkers::new(if n_threads > 1 { n_threads } else { 0 }),
+        }
+    }
+    pub fn reset(&mut self, options: &NewPuzzleOptions, seeds: &[u64]) -> Vec<Observation> {
+        assert_eq!(seeds.len(), self.envs.len(), "One seed is needed per env");
+        self.envs
+            .iter_mut()
+            .zip(seeds)
+            .map(|(env, seed)| env.reset(options, *seed))
+            .collect()
+    }
+    // Steps each environment with its action. Environments that are done stay done
+    // until they are reset. No environment is stepped if an action index is invalid.
+    pub fn step(&mut self, actions: &[usize]) -> Result<Vec<EnvStep>, String> {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "One action is needed per env"
+        );
+        let actions = self
+            .envs
+            .iter()
+            .zip(actions)
+            .map(|(env, action)| env.action(*action))
+            .collect::<Result<Vec<_>, _>>()?;
+        let n_threads = self
+            .envs
+            .len()
+            .div_ceil(MIN_ENVS_PER_THREAD)
+            .min(self.workers.jobs.len());
+        if n_threads <= 1 {
+            return Ok(step_envs(&mut self.envs, &actions));
+        }
+        let chunk_len = self.envs.len().div_ceil(n_threads);
+        let mut envs = std::mem::take(&mut self.envs);
+        let chunks = actions.chunks(chunk_len);
+        let n_chunks = chunks.len();
+        for (jobs, actions) in self.workers.jobs.iter().zip(chunks) {
+            let rest = envs.split_off(actions.len());
+            jobs.send((envs, actions.to_vec()))
+                .expect("Env thread stopped");
+            envs = rest;
+        }
+        let mut steps = Vec::with_capacity(actions.len());
+        for results in &self.workers.results[..n_chunks] {
+            let (envs, chunk_steps) = results.recv().expect("Env thread panicked");
+            self.envs.extend(envs);
+            steps.extend(chunk_steps);
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> NewPuzzleOptions {
+        let mut options =
+            NewPuzzleOptions::new(PuzzleVariant::Classic, &NewPuzzleSettings::default());
+        options.size = (3, 3);
+        options
+    }
+
+    #[test]
+    fn batch_steps_like_single_envs() {
+        let options = options();
+        let seeds = 0..4 * MIN_ENVS_PER_THREAD as u64;
+        let mut batch = BatchEnv::new(&options, seeds.clone());
+        let mut envs = seeds
+            .map(|seed| PuzzleEnv::new(&options, seed))
+            .collect::<Vec<_>>();
+        for step in 0..20 {
+            let actions = envs
+                .iter()
+                .enumerate()
+                .map(|(i, env)| (i + step) % env.action_space().len())
+                .collect::<Vec<_>>();
+            let steps = batch.step(&actions).expect("Invalid action");
+            assert_eq!(steps.len(), envs.len());
+            for ((env, action), batch_step) in envs.iter_mut().zip(&actions).zip(steps) {
+                let env_step = env.step(*action).expect("Invalid action");
+                assert_eq!(batch_step.observation, env_step.observation);
+                assert_eq!(batch_step.done, env_step.done);
+            }
+        }
+        for (batch_env, env) in batch.envs.iter().zip(&envs) {
+            assert_eq!(batch_env.puzzle().to_text(), env.puzzle().to_text());
+        }
+    }
+
+    #[test]
+    fn invalid_actions_are_rejected() {
+        let options = options();
+        let mut env = PuzzleEnv::new(&options, 0);
+        let observation = env.observation();
+        assert!(env.step(env.action_space().len()).is_err());
+        assert_eq!(env.observation(), observation);
+        let mut batch = BatchEnv::new(&options, [0, 1]);
+        assert!(batch.step(&[0, usize::MAX]).is_err());
+        assert_eq!(batch.envs[0].observation(), observation);
+    }
+}
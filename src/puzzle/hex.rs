@@ -0,0 +1,209 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_3};
+
+use bevy::render::{
+    mesh::{Indices, PrimitiveTopology},
+    render_asset::RenderAssetUsages,
+};
+
+use crate::prelude::*;
+
+// Hexagonal puzzles use pointy-top hexagons, in a grid where rows are going up
+// and odd rows are shifted half a tile to the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexDirection {
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+impl HexDirection {
+    pub fn opposite(&self) -> Self {
+        use HexDirection::*;
+        match self {
+            East => West,
+            West => East,
+            NorthEast => SouthWest,
+            NorthWest => SouthEast,
+            SouthEast => NorthWest,
+            SouthWest => NorthEast,
+        }
+    }
+}
+
+// Position of the neighbour of a cell in the given direction, if inside the grid
+pub fn hex_neighbour(position: Coord, direction: HexDirection, size: Coord) -> Option<Coord> {
+    use HexDirection::*;
+    let (y, x) = (position.0 as isize, position.1 as isize);
+    let odd_row = y % 2 == 1;
+    let (east_shift, west_shift) = if odd_row { (1, 0) } else { (0, -1) };
+    let (y, x) = match direction {
+        East => (y, x + 1),
+        West => (y, x - 1),
+        NorthEast => (y + 1, x + east_shift),
+        NorthWest => (y + 1, x + west_shift),
+        SouthEast => (y - 1, x + east_shift),
+        SouthWest => (y - 1, x + west_shift),
+    };
+    ((0..size.0 as isize).contains(&y) && (0..size.1 as isize).contains(&x))
+        .then_some((y as usize, x as usize))
+}
+
+// Circumradius of a tile, so that the full grid fits in the unit square
+pub fn hex_tile_radius(size: Coord) -> f32 {
+    let sqrt_3 = 3f32.sqrt();
+    (1. / (sqrt_3 * (size.1 as f32 + 0.5))).min(1. / (1.5 * (size.0 as f32 - 1.) + 2.))
+}
+
+// Width and height of the full grid
+pub fn hex_grid_extent(size: Coord) -> Vec2 {
+    let radius = hex_tile_radius(size);
+    Vec2::new(
+        3f32.sqrt() * radius * (size.1 as f32 + if size.0 > 1 { 0.5 } else { 0. }),
+        1.5 * radius * (size.0 as f32 - 1.) + 2. * radius,
+    )
+}
+
+pub fn hex_tile_translation_from_position(position: Coord, size: Coord) -> Vec3 {
+    let radius = hex_tile_radius(size);
+    let sqrt_3 = 3f32.sqrt();
+    let Vec2 {
+        x: width,
+        y: height,
+    } = hex_grid_extent(size);
+    let shift = if position.0 % 2 == 1 { 0.5 } else { 0. };
+    Vec3::new(
+        sqrt_3 * radius * (position.1 as f32 + 0.5 + shift) - width / 2.,
+        radius + 1.5 * radius * position.0 as f32 - height / 2.,
+        0.0,
+    )
+}
+
+// Corners of the top face of a tile with a circumradius of 0.5, counter clock-wise
+fn hex_corners() -> [Vec2; 6] {
+    std::array::from_fn(|i| 0.5 * Vec2::from_angle(FRAC_PI_2 + i as f32 * FRAC_PI_3))
+}
+
+// Hexagonal prism with a circumradius of 0.5 and a height of 1.
+// Vertices are: top center, 6 top corners, bottom center, 6 bottom corners, then 6 side quads
+pub fn compute_hex_tile_mesh(
+    size: Coord,
+    position: Coord,
+    flipped_x: bool,
+    flipped_y: bool,
+    double_sided: bool,
+) -> Mesh {
+    let corners = hex_corners();
+    let mut normals = vec![[0., 0., 1.]; 7];
+    normals.extend([[0., 0., -1.]; 7]);
+    let mut indices = vec![];
+    for i in 0..6u32 {
+        let next = (i + 1) % 6;
+        indices.extend([0, i + 1, next + 1]);
+        indices.extend([7, next + 8, i + 8]);
+        let side = 14 + 4 * i;
+        indices.extend([side, side + 1, side + 2, side, side + 2, side + 3]);
+        let normal = (corners[i as usize] + corners[next as usize]).normalize();
+        normals.extend([[normal.x, normal.y, 0.]; 4]);
+    }
+    // Outlines are extruded in the plane of the tile, away from its center
+    let outline_normals = hex_corners().map(|corner| corner.normalize().extend(0.).to_array());
+    let mut mesh_outline_normals = vec![];
+    for _ in 0..2 {
+        mesh_outline_normals.push([0., 0., 0.]);
+        mesh_outline_normals.extend(outline_normals);
+    }
+    for i in 0..6 {
+        let (corner, next) = (outline_normals[i], outline_normals[(i + 1) % 6]);
+        mesh_outline_normals.extend([corner, corner, next, next]);
+    }
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(ATTRIBUTE_OUTLINE_NORMAL, mesh_outline_normals)
+    .with_inserted_indices(Indices::U32(indices));
+    set_hex_tile_mesh_position(&mut mesh, None);
+    set_hex_tile_mesh_uvs(
+        &mut mesh,
+        size,
+        position,
+        flipped_x,
+        flipped_y,
+        double_sided,
+    );
+    mesh
+}
+
+pub fn set_hex_tile_mesh_uvs(
+    mesh: &mut Mesh,
+    size: Coord,
+    position: Coord,
+    flipped_x: bool,
+    flipped_y: bool,
+    double_sided: bool,
+) {
+    let mapping = TileUvMapping::new(
+        size,
+        position,
+        PuzzleShape::Hex,
+        flipped_x,
+        flipped_y,
+        double_sided,
+    );
+    let mut uvs = vec![mapping.uv(Vec2::ZERO, false)];
+    uvs.extend(hex_corners().map(|corner| mapping.uv(corner, false)));
+    if double_sided {
+        // The bottom face holds the hidden image, seen from behind
+        uvs.push(mapping.uv(Vec2::ZERO, true));
+        uvs.extend(hex_corners().map(|corner| mapping.uv(Vec2::new(-corner.x, corner.y), true)));
+    } else {
+        uvs.extend([[0.0, 0.0]; 7]);
+    }
+    // Other sides are uniform color of 0,0 pixel
+    uvs.extend([[0.0, 0.0]; 24]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+}
+
+// When given, the mesh is squashed along the (axis, ratio), ratio going from 1.0 for
+// the full tile to 0.0 for a flat line.
+pub fn set_hex_tile_mesh_position(mesh: &mut Mesh, squash: Option<(Vec2, f32)>) {
+    let corners = hex_corners().map(|corner| match squash {
+        Some((axis, ratio)) => corner - (1. - ratio) * corner.dot(axis) * axis,
+        None => corner,
+    });
+    let mut positions = vec![];
+    for z in [0.5, -0.5] {
+        positions.push([0., 0., z]);
+        positions.extend(corners.map(|corner| [corner.x, corner.y, z]));
+    }
+    for i in 0..6 {
+        let (corner, next) = (corners[i], corners[(i + 1) % 6]);
+        positions.extend([
+            [corner.x, corner.y, 0.5],
+            [corner.x, corner.y, -0.5],
+            [next.x, next.y, -0.5],
+            [next.x, next.y, 0.5],
+        ]);
+    }
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+}
+
+// Mouse location on a hexagonal tile, with x and y in [-0.5,0.5], to action.
+// Center moves the tile, sides flip it, and corners rotate it.
+pub fn action_from_hex_tip_local(x: f32, y: f32) -> PuzzleAction {
+    let local = Vec2::new(x, y);
+    if local.length() < 0.25 {
+        return PuzzleAction::MoveActive;
+    }
+    // Angle in [0,12[ twelfth of turn, starting from the East direction
+    let sector = (local.to_angle().to_degrees().rem_euclid(360.) / 30.) as usize;
+    match sector {
+        0 | 11 | 5 | 6 => PuzzleAction::ActiveFlipX,
+        2 | 3 | 8 | 9 => PuzzleAction::ActiveFlipY,
+        1 | 10 => PuzzleAction::ActiveRotateCW,
+        _ => PuzzleAction::ActiveRotateCCW,
+    }
+}
@@ -0,0 +1,261 @@
+use ab_glyph::{point, Font as _, FontRef, PxScale, ScaleFont};
+use bevy::render::{
+    render_asset::RenderAssetUsages,
+    render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::prelude::*;
+
+use super::tile_cell_scale;
+
+// Texture of a double-sided puzzle, built once both of its images are loaded.
+// Materials can use the texture handle right away, it is only filled later.
+#[derive(Component)]
+pub struct DoubleSidedImage {
+    pub front: Handle<Image>,
+    pub back: Handle<Image>,
+    pub texture: Handle<Image>,
+}
+
+pub fn compose_double_sided_images(
+    mut commands: Commands,
+    double_sided_images: Query<(Entity, &DoubleSidedImage)>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (entity, double_sided_image) in double_sided_images.iter() {
+        let (Some(front), Some(back)) = (
+            images.get(&double_sided_image.front),
+            images.get(&double_sided_image.back),
+        ) else {
+            continue;
+        };
+        let texture =
+            compose_double_sided_image(front, back).expect("Unsupported puzzle image format");
+        images.insert(&double_sided_image.texture, texture);
+        commands.entity(entity).remove::<DoubleSidedImage>();
+    }
+}
+
+// Front image on the left half, back image on the right half.
+// The back image is stretched to the size of the front one.
+pub fn compose_double_sided_image(front: &Image, back: &Image) -> Option<Image> {
+    let front = front.convert(TextureFormat::Rgba8UnormSrgb)?;
+    let back = back.convert(TextureFormat::Rgba8UnormSrgb)?;
+    let (width, height) = (front.width() as usize, front.height() as usize);
+    let (back_width, back_height) = (back.width() as usize, back.height() as usize);
+    let mut data = Vec::with_capacity(8 * width * height);
+    for y in 0..height {
+        data.extend_from_slice(&front.data[4 * y * width..4 * (y + 1) * width]);
+        let back_y = y * back_height / height;
+        for x in 0..width {
+            let i = 4 * (back_y * back_width + x * back_width / width);
+            data.extend_from_slice(&back.data[i..i + 4]);
+        }
+    }
+    Some(Image::new(
+        Extent3d {
+            width: 2 * width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all(),
+    ))
+}
+
+const PROCEDURAL_IMAGE_SIZE: usize = 1024;
+
+// Images generated at runtime, that need no network nor asset file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProceduralImage {
+    // Classic taquin, tiles are numbered from 1 in reading order
+    Numbered,
+    // Hue changes horizontally and lightness vertically
+    Gradient,
+    // Alternating colors, with an F marker on each tile that shows any rotation or flip
+    Checkerboard,
+}
+impl ProceduralImage {
+    pub fn generate(&self, size: Coord, shape: PuzzleShape) -> Image {
+        let mut canvas = Canvas::new(PROCEDURAL_IMAGE_SIZE);
+        match self {
+            ProceduralImage::Numbered => {
+                canvas.fill(|_| Srgba::rgb(0.93, 0.87, 0.75));
+                for row in 0..size.0 {
+                    for col in 0..size.1 {
+                        let number = (size.0 - 1 - row) * size.1 + col + 1;
+                        canvas.draw_text(
+                            &number.to_string(),
+                            tile_translation_from_position((row, col), size, shape).truncate(),
+                            0.4 * tile_cell_scale(size, shape),
+                            Srgba::rgb(0.17, 0.15, 0.27),
+                        );
+                    }
+                }
+            }
+            ProceduralImage::Gradient => {
+                canvas.fill(|point| {
+                    Color::hsl(300. * (point.x + 0.5), 0.8, 0.25 + 0.5 * (point.y + 0.5)).to_srgba()
+                });
+            }
+            ProceduralImage::Checkerboard => {
+                let colors = [
+                    Srgba::rgb(0.85, 0.85, 0.85),
+                    Srgba::rgb(0.35, 0.35, 0.45),
+                    Srgba::rgb(0.6, 0.4, 0.3),
+                ];
+                canvas.fill(|point| match cell_at(point, size, shape) {
+                    Some((row, col)) => match shape {
+                        PuzzleShape::Square => colors[(row + col) % 2],
+                        // 3 colors are needed for neighbour hexagons to always differ
+                        PuzzleShape::Hex => {
+                            let q = col as isize - (row as isize - (row as isize & 1)) / 2;
+                            colors[(q - row as isize).rem_euclid(3) as usize]
+                        }
+                    },
+                    None => Srgba::BLACK,
+                });
+                for row in 0..size.0 {
+                    for col in 0..size.1 {
+                        let cell_size = tile_cell_scale(size, shape);
+                        let center = tile_translation_from_position((row, col), size, shape);
+                        let color = if (row + col) % 2 == 0 {
+                            Srgba::rgb(0.8, 0.1, 0.1)
+                        } else {
+                            Srgba::rgb(1.0, 0.85, 0.2)
+                        };
+                        // Marker in the top left quarter of the tile
+                        canvas.draw_text(
+                            "F",
+                            center.truncate() + Vec2::new(-0.18, 0.18) * cell_size,
+                            0.4 * cell_size,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+        canvas.into_image()
+    }
+}
+
+// Grid cell containing the given point in puzzle coordinates, if any
+fn cell_at(point: Vec2, size: Coord, shape: PuzzleShape) -> Option<Coord> {
+    match shape {
+        PuzzleShape::Square => {
+            let cell = tile_cell_scale(size, shape);
+            let row = (point.y / cell + size.0 as f32 / 2.).floor() as isize;
+            let col = (point.x / cell + size.1 as f32 / 2.).floor() as isize;
+            ((0..size.0 as isize).contains(&row) && (0..size.1 as isize).contains(&col))
+                .then_some((row as usize, col as usize))
+        }
+        // The closest center is searched around the cell estimated from rows and columns spacing
+        PuzzleShape::Hex => {
+            let radius = hex_tile_radius(size);
+            let origin = hex_tile_translation_from_position((0, 0), size).truncate();
+            let row = ((point.y - origin.y) / (1.5 * radius)).round() as isize;
+            let col = ((point.x - origin.x) / (3f32.sqrt() * radius)).round() as isize;
+            (row - 1..=row + 1)
+                .flat_map(|row| (col - 1..=col + 1).map(move |col| (row, col)))
+                .filter(|(row, col)| {
+                    (0..size.0 as isize).contains(row) && (0..size.1 as isize).contains(col)
+                })
+                .map(|(row, col)| (row as usize, col as usize))
+                .map(|cell| {
+                    let center = tile_translation_from_position(cell, size, shape).truncate();
+                    (cell, center.distance(point))
+                })
+                .filter(|(_, distance)| *distance < radius)
+                .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+                .map(|(cell, _)| cell)
+        }
+    }
+}
+
+// Square RGBA image, drawn in puzzle coordinates where (0,0) is the center,
+// x goes right, y goes up, and the full image is [-0.5,0.5]
+struct Canvas {
+    size: usize,
+    data: Vec<u8>,
+}
+impl Canvas {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            data: vec![0; 4 * size * size],
+        }
+    }
+    fn to_point(&self, x: usize, y: usize) -> Vec2 {
+        Vec2::new(
+            (x as f32 + 0.5) / self.size as f32 - 0.5,
+            0.5 - (y as f32 + 0.5) / self.size as f32,
+        )
+    }
+    fn fill(&mut self, color_at: impl Fn(Vec2) -> Srgba) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let color = color_at(self.to_point(x, y));
+                let i = 4 * (y * self.size + x);
+                self.data[i..i + 4].copy_from_slice(&color.to_u8_array());
+            }
+        }
+    }
+    fn blend(&mut self, x: i32, y: i32, color: Srgba, coverage: f32) {
+        if x < 0 || y < 0 || x as usize >= self.size || y as usize >= self.size {
+            return;
+        }
+        let i = 4 * (y as usize * self.size + x as usize);
+        let coverage = coverage.clamp(0., 1.);
+        for (channel, value) in color.to_u8_array().into_iter().take(3).enumerate() {
+            let current = self.data[i + channel] as f32;
+            self.data[i + channel] = (current + (value as f32 - current) * coverage) as u8;
+        }
+    }
+    // Text is centered on the given point, height being the font size
+    fn draw_text(&mut self, text: &str, center: Vec2, height: f32, color: Srgba) {
+        let font = FontRef::try_from_slice(FIRA_MONO).expect("Invalid embedded font");
+        let scale = PxScale::from(height * self.size as f32);
+        let scaled_font = font.as_scaled(scale);
+        let width: f32 = text
+            .chars()
+            .map(|c| scaled_font.h_advance(font.glyph_id(c)))
+            .sum();
+        let mut x = (center.x + 0.5) * self.size as f32 - width / 2.;
+        let baseline = (0.5 - center.y) * self.size as f32
+            + (scaled_font.ascent() + scaled_font.descent()) / 2.;
+        for c in text.chars() {
+            let glyph = font
+                .glyph_id(c)
+                .with_scale_and_position(scale, point(x, baseline));
+            x += scaled_font.h_advance(glyph.id);
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                let mut pixels = vec![];
+                outlined.draw(|gx, gy, coverage| pixels.push((gx, gy, coverage)));
+                for (gx, gy, coverage) in pixels {
+                    self.blend(
+                        bounds.min.x as i32 + gx as i32,
+                        bounds.min.y as i32 + gy as i32,
+                        color,
+                        coverage,
+                    );
+                }
+            }
+        }
+    }
+    fn into_image(self) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.size as u32,
+                height: self.size as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            self.data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::all(),
+        )
+    }
+}
@@ -0,0 +1,159 @@
+use std::f32::consts::PI;
+
+use bevy::render::{
+    mesh::{Indices, PrimitiveTopology},
+    render_asset::RenderAssetUsages,
+};
+use grid::Grid;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::prelude::*;
+
+// Height of a tab, relative to the tile size.
+// Kept low enough for the tile outline to be star-shaped from its center.
+const JIGSAW_TAB_HEIGHT: f32 = 0.15;
+// Number of segments used for each side of a tile
+const JIGSAW_SIDE_SEGMENTS: usize = 16;
+
+// Jigsaw shape of the tiles of a square puzzle, where each inner edge between two tiles
+// is a tab on one of them and a blank on the other.
+#[derive(Debug, Clone)]
+pub struct JigsawEdges {
+    size: Coord,
+    // For the edge between (row,col) and (row,col+1), true if the tab is on the left tile
+    vertical_edges: Grid<bool>,
+    // For the edge between (row,col) and (row+1,col), true if the tab is on the bottom tile
+    horizontal_edges: Grid<bool>,
+}
+impl JigsawEdges {
+    pub fn new(size: Coord, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut random_grid = |rows: usize, cols: usize| {
+            Grid::from_vec((0..rows * cols).map(|_| rng.gen_bool(0.5)).collect(), cols)
+        };
+        Self {
+            size,
+            vertical_edges: random_grid(size.0, size.1.saturating_sub(1)),
+            horizontal_edges: random_grid(size.0.saturating_sub(1), size.1),
+        }
+    }
+    // Shape of each side of the tile at the given position, in order bottom, right, top, left.
+    // 1 for a tab, -1 for a blank, 0 for a flat side on the grid border.
+    pub fn tile_sides(&self, position: Coord) -> [f32; 4] {
+        let (row, col) = position;
+        let side = |tab: bool| if tab { 1. } else { -1. };
+        [
+            if row > 0 {
+                -side(self.horizontal_edges[(row - 1, col)])
+            } else {
+                0.
+            },
+            if col + 1 < self.size.1 {
+                side(self.vertical_edges[(row, col)])
+            } else {
+                0.
+            },
+            if row + 1 < self.size.0 {
+                side(self.horizontal_edges[(row, col)])
+            } else {
+                0.
+            },
+            if col > 0 {
+                -side(self.vertical_edges[(row, col - 1)])
+            } else {
+                0.
+            },
+        ]
+    }
+}
+
+// Outline of a jigsaw tile, counter clock-wise from the bottom left corner
+fn jigsaw_tile_outline(sides: [f32; 4]) -> Vec<Vec2> {
+    let corners = [
+        Vec2::new(-0.5, -0.5),
+        Vec2::new(0.5, -0.5),
+        Vec2::new(0.5, 0.5),
+        Vec2::new(-0.5, 0.5),
+    ];
+    let mut outline = vec![];
+    for (i, side) in sides.iter().enumerate() {
+        let (start, end) = (corners[i], corners[(i + 1) % 4]);
+        let outward = (end - start).perp() * -1.;
+        for segment in 0..JIGSAW_SIDE_SEGMENTS {
+            let t = segment as f32 / JIGSAW_SIDE_SEGMENTS as f32;
+            // Smooth bump on the middle half of the side
+            let bump = if (0.25..0.75).contains(&t) {
+                (1. - (2. * PI * (t - 0.25) / 0.5).cos()) / 2.
+            } else {
+                0.
+            };
+            outline.push(start.lerp(end, t) + outward * *side * JIGSAW_TAB_HEIGHT * bump);
+        }
+    }
+    outline
+}
+
+// Flat tile with jigsaw sides, triangulated as a fan from its center.
+// A flipped tile has its outline mirrored, so that its tabs move with its image.
+pub fn compute_jigsaw_tile_mesh(
+    size: Coord,
+    position: Coord,
+    flipped_x: bool,
+    flipped_y: bool,
+    double_sided: bool,
+    sides: [f32; 4],
+) -> Mesh {
+    let flip = Vec2::new(
+        if flipped_x { -1. } else { 1. },
+        if flipped_y { -1. } else { 1. },
+    );
+    let mut outline = jigsaw_tile_outline(sides)
+        .into_iter()
+        .map(|point| point * flip)
+        .collect::<Vec<_>>();
+    // A single flip reverses the winding, which must stay counter clock-wise
+    if flipped_x != flipped_y {
+        outline.reverse();
+    }
+    let mapping = TileUvMapping::new(
+        size,
+        position,
+        PuzzleShape::Square,
+        flipped_x,
+        flipped_y,
+        double_sided,
+    );
+    let points = [Vec2::ZERO].into_iter().chain(outline).collect::<Vec<_>>();
+    let n_outline = points.len() as u32 - 1;
+    let indices = (0..n_outline)
+        .flat_map(|i| [0, i + 1, (i + 1) % n_outline + 1])
+        .collect::<Vec<_>>();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        points
+            .iter()
+            .map(|point| point.extend(0.).to_array())
+            .collect::<Vec<_>>(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; points.len()])
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        points
+            .iter()
+            .map(|point| mapping.uv(*point, false))
+            .collect::<Vec<_>>(),
+    )
+    // Outlines are extruded in the plane of the tile, away from its center
+    .with_inserted_attribute(
+        ATTRIBUTE_OUTLINE_NORMAL,
+        points
+            .iter()
+            .map(|point| point.normalize_or_zero().extend(0.).to_array())
+            .collect::<Vec<_>>(),
+    )
+    .with_inserted_indices(Indices::U32(indices))
+}
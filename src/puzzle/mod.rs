@@ -11,18 +11,77 @@ use rand::seq::SliceRandom;
 use rand::Rng;
 use rand::RngCore;
 
+mod badge;
+mod config;
+mod env;
+mod hex;
+mod image;
+mod jigsaw;
+mod notation;
+mod solver;
 mod spawn;
+mod text;
+pub use badge::*;
+pub use config::*;
+pub use env::*;
+pub use hex::*;
+pub use image::*;
+pub use jigsaw::*;
+pub use notation::*;
+pub use solver::*;
 pub use spawn::*;
 // Coordinate for tile in the puzzle
 // .0 is the row
 // .1 is the column
 pub type Coord = (usize, usize);
 
-pub fn dist_of_1(p1: Coord, p2: Coord) -> bool {
-    [(0, 1), (1, 0)].contains(&(
-        (p1.0 as i32 - p2.0 as i32).abs(),
-        (p1.1 as i32 - p2.1 as i32).abs(),
-    ))
+// Direction, as (row,column) increments, to go from p1 to its neighbour p2.
+// When wrapping, tiles on opposite edges of a grid of the given size are neighbours.
+pub fn neighbour_direction(
+    p1: Coord,
+    p2: Coord,
+    size: Coord,
+    wrapping: bool,
+) -> Option<(isize, isize)> {
+    let delta = |from: usize, to: usize, len: usize| {
+        let delta = to as isize - from as isize;
+        match delta {
+            -1..=1 => Some(delta),
+            _ if wrapping && delta == len as isize - 1 => Some(-1),
+            _ if wrapping && delta == 1 - len as isize => Some(1),
+            _ => None,
+        }
+    };
+    match (delta(p1.0, p2.0, size.0), delta(p1.1, p2.1, size.1)) {
+        (Some(0), Some(dx)) if dx != 0 => Some((0, dx)),
+        (Some(dy), Some(0)) if dy != 0 => Some((dy, 0)),
+        _ => None,
+    }
+}
+pub fn dist_of_1(p1: Coord, p2: Coord, size: Coord, wrapping: bool) -> bool {
+    neighbour_direction(p1, p2, size, wrapping).is_some()
+}
+// Randomly flips and rotates a tile, with the given probabilities
+fn scramble_tile_orientation(
+    tile: &mut Tile,
+    flip_pct: f64,
+    rotation_pct: f64,
+    rng: &mut impl RngCore,
+) {
+    if rng.gen_bool(flip_pct) {
+        let what = rng.gen_range(1..=3u8);
+        if what & 1 == 1 {
+            tile.flip_x();
+        }
+        if what & 2 == 2 {
+            tile.flip_y();
+        }
+    }
+    if rng.gen_bool(rotation_pct) {
+        for _ in 0..(rng.gen_range(1..tile.rotation.order())) {
+            tile.rotate_cw();
+        }
+    }
 }
 // Tag component for the parent of all solution tiles
 #[derive(Component)]
@@ -38,42 +97,84 @@ pub struct PuzzleAssets {
     tile_scale: Vec3,
     // scale used by each tile when the puzzle is solved
     solved_tile_scale: Vec3,
-    // Color of the tile outline when the tile is both misplaced and misoriented
-    outline_color_misplaced_misoriented: Color,
-    // Color of the tile outline when the tile is misplaced
-    outline_color_misplaced: Color,
-    // Color of the tile outline when the tile is misoriented
-    outline_color_misoriented: Color,
-    // Color of the tile outline when the tile is the active tile
-    outline_color_active: Color,
+    // Material shared by all tiles showing the puzzle image
+    tile_material: Handle<StandardMaterial>,
+    tile_meshes: TileMeshPool,
+    // Factor applied to outline widths, so that they stay thinner than tiny tiles
+    outline_width_scale: f32,
     // Material containing icon for most Puzzle Actions
     action_tip_materials: HashMap<PuzzleAction, Handle<StandardMaterial>>,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Puzzle {
+    // For double-sided puzzles, the texture holds both the front and the back images
     pub image: Handle<Image>,
+    pub kind: PuzzleKind,
+    pub shape: PuzzleShape,
     pub active: Coord,
-    pub hole: Coord,
+    // Positions of the holes, empty for puzzle kinds without a hole
+    pub holes: Vec<Coord>,
+    // Index in holes of the hole targeted by move actions
+    pub active_hole: usize,
+    // When true the grid wraps around, tiles on an edge can move to the opposite edge
+    pub wrapping: bool,
     pub tiles: Grid<Option<Tile>>,
+    pub double_sided: bool,
+    // In expert mode, a double-sided puzzle is solved once both of its images have been
+    // completed. As they can't be shown at the same time, the front must be completed first.
+    pub expert: bool,
+    pub front_completed: bool,
+    // Shape of the tile edges of a jigsaw puzzle, None for straight edges
+    pub jigsaw_edges: Option<JigsawEdges>,
     pub is_solved: bool,
     pub show_errors: bool,
-    pub hole_entity: Option<Entity>,
+    // When spawned, Bevy entities associated to each hole, in the same order as holes
+    pub hole_entities: Vec<Entity>,
     pub action_tip_entity: Option<Entity>,
     pub actions_count: usize,
+    // Actions applied by the player, see record_action
+    pub history: Vec<PuzzleAction>,
 }
 impl Puzzle {
-    pub fn new(
-        image: Handle<Image>,
-        width: usize,
-        height: usize,
-        random_hole: bool,
-        mut rng: impl RngCore,
-    ) -> Self {
-        let hole = if random_hole {
-            (rng.gen_range(0..height), rng.gen_range(0..width))
-        } else {
-            (0, width - 1)
+    pub fn new(image: Handle<Image>, config: &PuzzleConfig, mut rng: impl RngCore) -> Self {
+        let (height, width) = config.size();
+        assert!(
+            config.shape == PuzzleShape::Square
+                || (config.kind == PuzzleKind::Taquin && !config.wrapping),
+            "Hexagonal puzzles only support Taquin without wrapping"
+        );
+        assert!(
+            !config.jigsaw || (config.shape == PuzzleShape::Square && !config.double_sided),
+            "Jigsaw edges are only supported by single-sided square puzzles"
+        );
+        let mut free_cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (y, x)))
+            .collect::<Vec<_>>();
+        let (holes, walls) = match config.kind {
+            PuzzleKind::Taquin => {
+                assert!(
+                    config.n_holes >= 1 && config.n_holes + config.n_walls < width * height,
+                    "Invalid number of holes and walls for a {}x{} puzzle",
+                    width,
+                    height
+                );
+                let holes = if config.random_hole {
+                    free_cells.shuffle(&mut rng);
+                    free_cells.drain(..config.n_holes).collect::<Vec<_>>()
+                } else {
+                    // Fill from the bottom right corner, going left then up
+                    let holes = (0..config.n_holes)
+                        .map(|i| (i / width, width - 1 - i % width))
+                        .collect::<Vec<_>>();
+                    free_cells.retain(|cell| !holes.contains(cell));
+                    free_cells.shuffle(&mut rng);
+                    holes
+                };
+                let walls = free_cells.drain(..config.n_walls).collect::<Vec<_>>();
+                (holes, walls)
+            }
+            PuzzleKind::Loopover => (vec![], vec![]),
         };
         let puzzle_size = (height, width);
         let tiles = Grid::from_vec(
@@ -81,10 +182,13 @@ impl Puzzle {
                 .flat_map(|y| {
                     (0..width)
                         .map(|x| {
-                            if (y, x) == hole {
+                            if holes.contains(&(y, x)) {
                                 None
                             } else {
-                                Some(Tile::new((y, x), puzzle_size))
+                                let mut tile = Tile::new((y, x), puzzle_size, config.shape);
+                                tile.fixed = walls.contains(&(y, x));
+                                tile.double_sided = config.double_sided;
+                                Some(tile)
                             }
                         })
                         .collect::<Vec<_>>()
@@ -94,14 +198,25 @@ impl Puzzle {
         );
         Puzzle {
             image,
-            active: hole,
-            hole,
+            kind: config.kind,
+            shape: config.shape,
+            active: holes.first().copied().unwrap_or((0, 0)),
+            holes,
+            active_hole: 0,
+            wrapping: config.wrapping,
             tiles,
+            double_sided: config.double_sided,
+            expert: config.double_sided && config.expert,
+            front_completed: false,
+            jigsaw_edges: config
+                .jigsaw
+                .then(|| JigsawEdges::new(puzzle_size, rng.next_u64())),
             is_solved: false,
             show_errors: false,
-            hole_entity: None,
+            hole_entities: vec![],
             action_tip_entity: None,
             actions_count: 0,
+            history: vec![],
         }
     }
     pub fn get_active_tile_mut(&mut self) -> &mut Option<Tile> {
@@ -109,9 +224,62 @@ impl Puzzle {
             .get_mut(self.active.0, self.active.1)
             .expect("Invalid Active tile")
     }
+    // True if the active position contains a tile that can be flipped and rotated
+    pub fn is_active_tile_movable(&self) -> bool {
+        self.tiles[self.active]
+            .as_ref()
+            .is_some_and(|tile| !tile.fixed)
+    }
     pub fn size(&self) -> Coord {
         self.tiles.size()
     }
+    // Index of a hole next to the given position, preferring the active hole
+    fn hole_next_to(&self, position: Coord) -> Option<usize> {
+        (0..self.holes.len())
+            .filter(|i| self.move_action_between(position, self.holes[*i]).is_some())
+            .min_by_key(|i| *i != self.active_hole)
+    }
+    // Position of the tile that a move action brings into the hole, if inside the grid
+    fn move_source(&self, hole: Coord, action: PuzzleAction) -> Option<Coord> {
+        let size = self.size();
+        match self.shape {
+            PuzzleShape::Square => {
+                let (dy, dx) = action.direction()?;
+                let (y, x) = (hole.0 as isize - dy, hole.1 as isize - dx);
+                let (y, x) = if self.wrapping {
+                    (y.rem_euclid(size.0 as isize), x.rem_euclid(size.1 as isize))
+                } else {
+                    (y, x)
+                };
+                ((0..size.0 as isize).contains(&y) && (0..size.1 as isize).contains(&x))
+                    .then_some((y as usize, x as usize))
+            }
+            PuzzleShape::Hex => hex_neighbour(hole, action.hex_direction()?.opposite(), size),
+        }
+    }
+    // Move action that brings the tile at source into the neighbouring destination
+    fn move_action_between(&self, source: Coord, destination: Coord) -> Option<PuzzleAction> {
+        PuzzleAction::MOVES
+            .into_iter()
+            .find(|action| self.move_source(destination, *action) == Some(source))
+    }
+    fn is_movable(&self, position: Coord) -> bool {
+        self.tiles[position]
+            .as_ref()
+            .is_some_and(|tile| !tile.fixed)
+    }
+    // Make the hole at the active position, if any, the target of move actions
+    fn select_active_hole(&mut self) {
+        if let Some(i) = self.holes.iter().position(|hole| *hole == self.active) {
+            self.active_hole = i;
+        }
+    }
+    pub fn select_next_hole(&mut self) {
+        if !self.holes.is_empty() {
+            self.active_hole = (self.active_hole + 1) % self.holes.len();
+            self.active = self.holes[self.active_hole];
+        }
+    }
     pub fn shuffle(
         &mut self,
         n_moves: usize,
@@ -119,40 +287,68 @@ impl Puzzle {
         rotation_pct: f64,
         mut rng: impl RngCore,
     ) {
+        let size = self.size();
         let mut reverse_move = None;
         for _ in 0..n_moves {
+            match self.kind {
+                PuzzleKind::Taquin => {
+                    // Moves act on the active hole, alternate randomly between holes
+                    self.active_hole = rng.gen_range(0..self.holes.len());
+                }
+                PuzzleKind::Loopover => {
+                    // Shifts act on the row or column of the active tile, so pick a new one each time
+                    self.active = (rng.gen_range(0..size.0), rng.gen_range(0..size.1));
+                }
+            }
             let mut possible_moves = self.get_valid_moves();
             possible_moves.retain(|action| Some(*action) != reverse_move);
-            let action = possible_moves
-                .choose(&mut rng)
-                .expect("No possible move found");
+            // A hole can be fully surrounded by walls and other holes
+            let Some(action) = possible_moves.choose(&mut rng) else {
+                continue;
+            };
             reverse_move = Some(action.reverse());
-            self.apply_move_event(*action);
-            if let Some(active_tile) = self.get_active_tile_mut() {
-                if rng.gen_bool(flip_pct) {
-                    let what = rng.gen_range(1..=3u8);
-                    if what & 1 == 1 {
-                        active_tile.flip_x();
-                    }
-                    if what & 2 == 2 {
-                        active_tile.flip_y();
-                    }
+            match self.kind {
+                PuzzleKind::Taquin => {
+                    self.apply_move_event(*action);
                 }
-                if rng.gen_bool(rotation_pct) {
-                    for _ in 0..(rng.gen_range(1..=3u8)) {
-                        active_tile.rotate_cw();
-                    }
+                PuzzleKind::Loopover => {
+                    self.apply_shift_event(*action);
                 }
             }
+            if let Some(active_tile) = self
+                .get_active_tile_mut()
+                .as_mut()
+                .filter(|tile| !tile.fixed)
+            {
+                scramble_tile_orientation(active_tile, flip_pct, rotation_pct, &mut rng);
+            }
         }
         // After a shuffle we want the active 'tile' to be the hole, not the last moved tiled during shuffling
-        self.active = self.hole;
+        self.active_hole = 0;
+        if let Some(hole) = self.holes.first() {
+            self.active = *hole;
+        }
+        self.front_completed = false;
+        self.is_solved = false;
+    }
+    // Flips and rotates tiles in place, for a puzzle scrambled without moving its tiles
+    pub fn scramble_orientations(
+        &mut self,
+        flip_pct: f64,
+        rotation_pct: f64,
+        mut rng: impl RngCore,
+    ) {
+        for tile in self.tiles.iter_mut().flatten().filter(|tile| !tile.fixed) {
+            scramble_tile_orientation(tile, flip_pct, rotation_pct, &mut rng);
+        }
+        self.front_completed = false;
         self.is_solved = false;
     }
     pub fn compute_solved(&mut self) {
         let mut incorrect_placement = 0;
         let mut incorrect_flip = 0;
         let mut incorrect_rotation = 0;
+        let mut incorrect_back_orientation = 0;
         for (coord, tile) in self.tiles.indexed_iter() {
             if let Some(tile) = tile {
                 if tile.position != coord {
@@ -164,54 +360,235 @@ impl Puzzle {
                 if tile.is_rotated() {
                     incorrect_rotation += 1;
                 }
+                if !tile.is_back_correctly_oriented() {
+                    incorrect_back_orientation += 1;
+                }
             }
         }
-        self.is_solved = incorrect_placement == 0 && incorrect_flip == 0 && incorrect_rotation == 0;
+        let front_complete =
+            incorrect_placement == 0 && incorrect_flip == 0 && incorrect_rotation == 0;
+        if self.expert {
+            self.front_completed |= front_complete;
+            self.is_solved =
+                self.front_completed && incorrect_placement == 0 && incorrect_back_orientation == 0;
+        } else {
+            self.is_solved = front_complete;
+        }
+    }
+    // True if the back image is the one currently to be completed
+    pub fn targets_back(&self) -> bool {
+        self.expert && self.front_completed
     }
     pub fn apply_move_event(&mut self, event: PuzzleAction) -> (Option<Entity>, Coord, Coord) {
         use PuzzleAction::*;
         if self.is_solved {
             warn!("Move event while puzzle is solved");
         }
-        let mut position = self.hole;
-        let size = self.size();
-        match event {
-            MoveLeft => position.1 = (position.1 + 1).min(size.1 - 1),
-            MoveRight => position.1 = position.1.max(1) - 1,
-            MoveUp => position.0 = position.0.max(1) - 1,
-            MoveDown => position.0 = (position.0 + 1).min(size.0 - 1),
-            MoveActive => position = self.active,
+        let hole = *self
+            .holes
+            .get(self.active_hole)
+            .expect("Move event on a puzzle without hole");
+        let position = match event {
+            MoveActive => {
+                Some(self.active).filter(|active| self.move_action_between(*active, hole).is_some())
+            }
+            _ if event.is_move() => self.move_source(hole, event),
             _ => panic!("Not a Move event: {:?}", event),
-        }
-        if !dist_of_1(self.hole, position) {
-            (None, self.hole, self.hole)
-        } else if let Some(tile) = self.tiles.get_mut(position.0, position.1).unwrap().take() {
-            let destination = self.hole;
+        };
+        let Some(position) = position.filter(|position| self.is_movable(*position)) else {
+            return (None, hole, hole);
+        };
+        if let Some(tile) = self.tiles.get_mut(position.0, position.1).unwrap().take() {
+            let destination = hole;
             self.active = destination;
             let entity = tile.entity;
             self.tiles[destination] = Some(tile);
-            self.hole = position;
+            self.holes[self.active_hole] = position;
             (entity, destination, position)
         } else {
-            (None, self.hole, self.hole)
+            (None, hole, hole)
+        }
+    }
+    // Cyclically shift the row or column of the active tile.
+    // Returns for each tile its entity, source and destination
+    pub fn apply_shift_event(
+        &mut self,
+        event: PuzzleAction,
+    ) -> Vec<(Option<Entity>, Coord, Coord)> {
+        use PuzzleAction::*;
+        if self.is_solved {
+            warn!("Shift event while puzzle is solved");
+        }
+        let (height, width) = self.size();
+        let (row, column) = self.active;
+        let sources = match event {
+            ShiftRowLeft | ShiftRowRight => (0..width).map(|x| (row, x)).collect::<Vec<_>>(),
+            ShiftColumnUp | ShiftColumnDown => (0..height).map(|y| (y, column)).collect(),
+            _ => panic!("Not a Shift event: {:?}", event),
+        };
+        let destination = |(y, x): Coord| match event {
+            ShiftRowLeft => (y, (x + width - 1) % width),
+            ShiftRowRight => (y, (x + 1) % width),
+            ShiftColumnUp => ((y + 1) % height, x),
+            ShiftColumnDown => ((y + height - 1) % height, x),
+            _ => unreachable!(),
+        };
+        let tiles = sources
+            .iter()
+            .map(|source| self.tiles[*source].take())
+            .collect::<Vec<_>>();
+        let mut moves = vec![];
+        for (source, tile) in sources.into_iter().zip(tiles) {
+            let destination = destination(source);
+            moves.push((
+                tile.as_ref().and_then(|tile| tile.entity),
+                source,
+                destination,
+            ));
+            self.tiles[destination] = tile;
         }
+        // The active tile follows its row or column
+        self.active = destination(self.active);
+        moves
     }
     pub fn convert_action(&self, action: PuzzleAction) -> PuzzleAction {
         use PuzzleAction::*;
+        match (self.kind, action) {
+            (PuzzleKind::Taquin, MoveActive) => self
+                .hole_next_to(self.active)
+                .filter(|_| self.is_active_tile_movable())
+                .and_then(|i| self.move_action_between(self.active, self.holes[i]))
+                .unwrap_or(NoAction),
+            (PuzzleKind::Taquin, MoveUp | MoveDown) if self.shape == PuzzleShape::Hex => {
+                // There is no vertical move on hexagonal grids, use the first possible diagonal
+                let diagonals = if action == MoveUp {
+                    [MoveUpLeft, MoveUpRight]
+                } else {
+                    [MoveDownLeft, MoveDownRight]
+                };
+                let hole = self.holes[self.active_hole];
+                diagonals
+                    .into_iter()
+                    .find(|diagonal| self.move_source(hole, *diagonal).is_some())
+                    .unwrap_or(NoAction)
+            }
+            // Loopover has no hole, moves are shifts of the active tile row or column
+            (PuzzleKind::Loopover, MoveActive) => NoAction,
+            (PuzzleKind::Loopover, MoveLeft) => ShiftRowLeft,
+            (PuzzleKind::Loopover, MoveRight) => ShiftRowRight,
+            (PuzzleKind::Loopover, MoveUp) => ShiftColumnUp,
+            (PuzzleKind::Loopover, MoveDown) => ShiftColumnDown,
+            _ => action,
+        }
+    }
+    // Applies an action to the puzzle model, counting and recording it.
+    // Returns what changed, for the action handler to animate it.
+    pub fn apply_action(&mut self, action: PuzzleAction) -> ActionEffect {
+        use PuzzleAction::*;
+        if self.is_solved {
+            return ActionEffect::None;
+        }
         if action == MoveActive {
-            match (
-                (self.hole.0 as i32 - self.active.0 as i32),
-                (self.hole.1 as i32 - self.active.1 as i32),
-            ) {
-                (0, 1) => MoveRight,
-                (0, -1) => MoveLeft,
-                (1, 0) => MoveUp,
-                (-1, 0) => MoveDown,
-                _ => NoAction,
+            self.select_hole_next_to_active();
+        }
+        let selection = (self.active, self.active_hole);
+        let event = self.convert_action(action);
+        let effect = match event {
+            NoAction => ActionEffect::None,
+            MoveLeft | MoveRight | MoveUp | MoveDown | MoveUpLeft | MoveUpRight | MoveDownLeft
+            | MoveDownRight | MoveActive => {
+                let (entity, destination, source) = self.apply_move_event(event);
+                if source != destination {
+                    ActionEffect::Moves {
+                        action: event,
+                        moves: vec![(entity, source, destination)],
+                    }
+                } else {
+                    ActionEffect::Invalid
+                }
             }
-        } else {
-            action
+            ShiftRowLeft | ShiftRowRight | ShiftColumnUp | ShiftColumnDown => ActionEffect::Moves {
+                action: event,
+                moves: self.apply_shift_event(event),
+            },
+            MoveActiveLeft | MoveActiveRight | MoveActiveUp | MoveActiveDown | SetActive(_)
+            | SetActivePosition(_) | NextHole => {
+                match event {
+                    SetActive(entity) => self.set_active_entity(&entity),
+                    SetActivePosition(position) => self.set_active_position(position),
+                    NextHole => self.select_next_hole(),
+                    _ => self.apply_move_active_event(event),
+                }
+                if (self.active, self.active_hole) != selection {
+                    ActionEffect::Selection
+                } else {
+                    ActionEffect::None
+                }
+            }
+            ActiveFlipX | ActiveFlipY | ActiveRotateCW | ActiveRotateCCW => {
+                match self
+                    .get_active_tile_mut()
+                    .as_mut()
+                    .filter(|tile| !tile.fixed)
+                {
+                    Some(tile) => {
+                        let start = tile.clone();
+                        match event {
+                            ActiveFlipX | ActiveFlipY => ActionEffect::Flip {
+                                local_flip: tile.screen_flip(event),
+                                start,
+                                end: tile.clone(),
+                            },
+                            _ => {
+                                tile.apply_orientation_action(event);
+                                ActionEffect::Rotation {
+                                    start,
+                                    end: tile.clone(),
+                                }
+                            }
+                        }
+                    }
+                    None => ActionEffect::Invalid,
+                }
+            }
+        };
+        if effect.changed() {
+            if !matches!(effect, ActionEffect::Selection) {
+                self.actions_count += 1;
+            }
+            self.record_action(event);
+        }
+        self.compute_solved();
+        effect
+    }
+    // Move actions target the active hole, select the one next to the active tile
+    pub fn select_hole_next_to_active(&mut self) {
+        if let Some(i) = self.hole_next_to(self.active) {
+            if i != self.active_hole {
+                self.active_hole = i;
+                // Selecting the hole itself targets it the same way when replayed
+                self.record_action(PuzzleAction::SetActivePosition(self.holes[i]));
+            }
+        }
+    }
+    // Keeps an applied action in the history, for the move notation.
+    // Selections are kept as the selected position, consecutive ones are merged.
+    pub fn record_action(&mut self, action: PuzzleAction) {
+        use PuzzleAction::*;
+        let position = match action {
+            NoAction => return,
+            SetActivePosition(position) => position,
+            MoveActiveLeft | MoveActiveRight | MoveActiveUp | MoveActiveDown | SetActive(_)
+            | NextHole => self.active,
+            _ => {
+                self.history.push(action);
+                return;
+            }
+        };
+        if let Some(SetActivePosition(_)) = self.history.last() {
+            self.history.pop();
         }
+        self.history.push(SetActivePosition(position));
     }
     pub fn apply_move_active_event(&mut self, event: PuzzleAction) {
         use PuzzleAction::*;
@@ -228,6 +605,13 @@ impl Puzzle {
             _ => panic!("Not a MoveActive event: {:?}", event),
         }
         self.active = position;
+        self.select_active_hole();
+    }
+    // Selects the tile or hole at the given position, clamped inside the grid
+    pub fn set_active_position(&mut self, position: Coord) {
+        let size = self.size();
+        self.active = (position.0.min(size.0 - 1), position.1.min(size.1 - 1));
+        self.select_active_hole();
     }
     pub fn set_active_entity(&mut self, ref_entity: &Entity) {
         if let Some(coord) = self
@@ -241,65 +625,190 @@ impl Puzzle {
             .find_map(|(coord, entity)| (&entity == ref_entity).then_some(coord))
         {
             self.active = coord;
+        } else if let Some(i) = self
+            .hole_entities
+            .iter()
+            .position(|entity| entity == ref_entity)
+        {
+            self.active = self.holes[i];
+            self.active_hole = i;
+        }
+    }
+    // Move actions of the grid shape
+    fn move_candidates(&self) -> &'static [PuzzleAction] {
+        use PuzzleAction::*;
+        match self.shape {
+            PuzzleShape::Square => &[MoveUp, MoveDown, MoveRight, MoveLeft],
+            PuzzleShape::Hex => &[
+                MoveLeft,
+                MoveRight,
+                MoveUpLeft,
+                MoveUpRight,
+                MoveDownLeft,
+                MoveDownRight,
+            ],
         }
     }
     fn get_valid_moves(&self) -> Vec<PuzzleAction> {
         use PuzzleAction::*;
-        let size = self.size();
         let mut actions = vec![];
-        if self.hole.0 > 0 {
-            actions.push(MoveUp);
-        }
-        if self.hole.0 < size.0 - 1 {
-            actions.push(MoveDown);
-        }
-        if self.hole.1 > 0 {
-            actions.push(MoveRight);
-        }
-        if self.hole.1 < size.1 - 1 {
-            actions.push(MoveLeft);
+        match self.holes.get(self.active_hole) {
+            Some(hole) => {
+                // Keep only moves that bring a movable tile into the hole
+                actions.extend(self.move_candidates().iter().copied().filter(|action| {
+                    self.move_source(*hole, *action)
+                        .is_some_and(|source| self.is_movable(source))
+                }));
+            }
+            None => {
+                actions.extend([ShiftRowLeft, ShiftRowRight, ShiftColumnUp, ShiftColumnDown]);
+            }
         }
         actions
     }
+    // Errors of the tile at the given index, as (misplaced, misoriented)
+    pub fn tile_errors(&self, index: Coord, tile: &Tile) -> (bool, bool) {
+        let correctly_oriented = if self.targets_back() {
+            tile.is_back_correctly_oriented()
+        } else {
+            tile.is_correctly_oriented()
+        };
+        (index != tile.position, !correctly_oriented)
+    }
+    // Fraction of the movable tiles at their position and correctly oriented
+    pub fn progress(&self) -> f32 {
+        let (correct, total) = self
+            .tiles
+            .indexed_iter()
+            .filter_map(|(index, tile)| Some((index, tile.as_ref().filter(|tile| !tile.fixed)?)))
+            .fold((0, 0), |(correct, total), (index, tile)| {
+                let is_correct = self.tile_errors(index, tile) == (false, false);
+                (correct + is_correct as usize, total + 1)
+            });
+        if total == 0 {
+            1.
+        } else {
+            correct as f32 / total as f32
+        }
+    }
     pub fn show_outlines(
-        &mut self,
+        &self,
         outlines: &mut Query<&mut OutlineVolume>,
         assets: &PuzzleAssets,
+        theme: &Theme,
     ) {
-        self.tiles.indexed_iter_mut().for_each(|(index, tile)| {
+        self.tiles.indexed_iter().for_each(|(index, tile)| {
             if let Some(tile) = tile {
                 if let Some(entity) = tile.entity {
                     if let Ok(mut outline) = outlines.get_mut(entity) {
-                        let show_misplaced = self.show_errors && index != tile.position;
-                        let show_misoriented = self.show_errors && !tile.is_correctly_oriented();
+                        let (misplaced, misoriented) = self.tile_errors(index, tile);
+                        let show_misplaced = self.show_errors && misplaced;
+                        let show_misoriented = self.show_errors && misoriented;
                         let show_active =
                             self.active == index && !show_misoriented && !show_misplaced;
                         outline.visible =
                             !self.is_solved && (show_active || show_misplaced || show_misoriented);
                         outline.colour = match (show_misplaced, show_misoriented) {
-                            (true, true) => assets.outline_color_misplaced_misoriented,
-                            (true, false) => assets.outline_color_misplaced,
-                            (false, true) => assets.outline_color_misoriented,
-                            _ => assets.outline_color_active,
+                            (true, true) => theme.outline_misplaced_misoriented,
+                            (true, false) => theme.outline_misplaced,
+                            (false, true) => theme.outline_misoriented,
+                            _ => theme.outline_active,
                         };
-                        outline.width = if show_active { 1.0 } else { 3.0 };
+                        outline.width =
+                            assets.outline_width_scale * if show_active { 1.0 } else { 3.0 };
                     }
                 }
             }
         });
-        if let Some(entity) = self.hole_entity {
-            if let Ok(mut outline) = outlines.get_mut(entity) {
-                outline.visible = self.hole == self.active;
+        // With several holes, the one targeted by move actions is always shown
+        for (i, entity) in self.hole_entities.iter().enumerate() {
+            if let Ok(mut outline) = outlines.get_mut(*entity) {
+                outline.visible =
+                    i == self.active_hole && (self.holes.len() > 1 || self.holes[i] == self.active);
+                outline.colour = theme.outline_active;
+                outline.width = assets.outline_width_scale
+                    * if self.holes[i] == self.active {
+                        2.0
+                    } else {
+                        1.0
+                    };
             }
         }
     }
 }
-fn tile_translation_from_position(position: (usize, usize), size: (usize, usize)) -> Vec3 {
-    Vec3::new(
-        (2 * position.1 as isize + 1 - size.1 as isize) as f32 / (2 * size.1) as f32,
-        (2 * position.0 as isize + 1 - size.0 as isize) as f32 / (2 * size.0) as f32,
-        0.0,
-    )
+pub fn tile_translation_from_position(
+    position: (usize, usize),
+    size: (usize, usize),
+    shape: PuzzleShape,
+) -> Vec3 {
+    match shape {
+        // Cells are always square, a rectangular grid is centered in the unit square
+        PuzzleShape::Square => {
+            let cell = tile_cell_scale(size, shape);
+            Vec3::new(
+                (position.1 as f32 + 0.5 - size.1 as f32 / 2.) * cell,
+                (position.0 as f32 + 0.5 - size.0 as f32 / 2.) * cell,
+                0.0,
+            )
+        }
+        PuzzleShape::Hex => hex_tile_translation_from_position(position, size),
+    }
+}
+// Scale of a tile to exactly cover its cell
+fn tile_cell_scale(size: (usize, usize), shape: PuzzleShape) -> f32 {
+    match shape {
+        PuzzleShape::Square => 1. / (size.0.max(size.1) as f32),
+        PuzzleShape::Hex => 2. * hex_tile_radius(size),
+    }
+}
+// Width and height of the full grid, which fits in the unit square
+pub fn puzzle_extent(size: Coord, shape: PuzzleShape) -> Vec2 {
+    match shape {
+        PuzzleShape::Square => {
+            Vec2::new(size.1 as f32, size.0 as f32) * tile_cell_scale(size, shape)
+        }
+        PuzzleShape::Hex => hex_grid_extent(size),
+    }
+}
+// Animation of a tile sliding from source to destination.
+// When wrap_direction is given, as (row,column) increments, the tile slides out of the grid
+// in that direction and comes back in from the opposite edge.
+fn tile_slide_tween(
+    source: Coord,
+    destination: Coord,
+    wrap_direction: Option<(isize, isize)>,
+    size: Coord,
+    shape: PuzzleShape,
+    duration: Duration,
+) -> Sequence<Transform> {
+    let start = tile_translation_from_position(source, size, shape);
+    let end = tile_translation_from_position(destination, size, shape);
+    if let Some(direction) = wrap_direction {
+        let step =
+            Vec3::new(direction.1 as f32, direction.0 as f32, 0.0) * tile_cell_scale(size, shape);
+        Tween::new(
+            EaseFunction::QuadraticIn,
+            duration / 2,
+            TransformPositionLens {
+                start,
+                end: start + step,
+            },
+        )
+        .then(Tween::new(
+            EaseFunction::QuadraticOut,
+            duration / 2,
+            TransformPositionLens {
+                start: end - step,
+                end,
+            },
+        ))
+    } else {
+        Sequence::new([Tween::new(
+            EaseFunction::QuadraticInOut,
+            duration,
+            TransformPositionLens { start, end },
+        )])
+    }
 }
 
 #[derive(Component)]
@@ -307,18 +816,52 @@ pub struct ActionTip;
 #[derive(Component)]
 pub struct ActionTipIcon;
 
+// Marks the puzzle receiving the input of the player, and the PuzzleAction events
+#[derive(Component)]
+pub struct FocusedPuzzle;
+
+// Position of a puzzle among the puzzles shown side by side, from left to right.
+// Puzzles without a slot are placed after the others.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PuzzleSlot(pub usize);
+
+// Action on a given puzzle, whether it is focused or not
+#[derive(Debug, Event, Clone, Copy, PartialEq, Eq)]
+pub struct PuzzleActionEvent {
+    pub puzzle: Entity,
+    pub action: PuzzleAction,
+}
+
+// Sent when the player solves a puzzle, for apps embedding the game
+#[derive(Debug, Event, Clone, Copy, PartialEq, Eq)]
+pub struct PuzzleSolvedEvent {
+    pub puzzle: Entity,
+    pub actions_count: usize,
+}
+
 #[derive(Debug, Event, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PuzzleAction {
     MoveLeft,
     MoveRight,
     MoveUp,
     MoveDown,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
     MoveActive,
     MoveActiveLeft,
     MoveActiveRight,
     MoveActiveUp,
     MoveActiveDown,
     SetActive(Entity),
+    // Selects the tile or hole at a position, used by the move notation
+    SetActivePosition(Coord),
+    NextHole,
+    ShiftRowLeft,
+    ShiftRowRight,
+    ShiftColumnUp,
+    ShiftColumnDown,
     ActiveFlipX,
     ActiveFlipY,
     ActiveRotateCW,
@@ -326,6 +869,20 @@ pub enum PuzzleAction {
     NoAction,
 }
 impl PuzzleAction {
+    // All actions moving a tile into a hole
+    pub const MOVES: [PuzzleAction; 8] = [
+        PuzzleAction::MoveLeft,
+        PuzzleAction::MoveRight,
+        PuzzleAction::MoveUp,
+        PuzzleAction::MoveDown,
+        PuzzleAction::MoveUpLeft,
+        PuzzleAction::MoveUpRight,
+        PuzzleAction::MoveDownLeft,
+        PuzzleAction::MoveDownRight,
+    ];
+    pub fn is_move(&self) -> bool {
+        PuzzleAction::MOVES.contains(self)
+    }
     pub fn reverse(&self) -> PuzzleAction {
         use PuzzleAction::*;
         match self {
@@ -333,174 +890,368 @@ impl PuzzleAction {
             MoveRight => MoveLeft,
             MoveUp => MoveDown,
             MoveDown => MoveUp,
+            MoveUpLeft => MoveDownRight,
+            MoveDownRight => MoveUpLeft,
+            MoveUpRight => MoveDownLeft,
+            MoveDownLeft => MoveUpRight,
             MoveActiveLeft => MoveActiveRight,
             MoveActiveRight => MoveActiveLeft,
             MoveActiveUp => MoveActiveDown,
             MoveActiveDown => MoveActiveUp,
+            ShiftRowLeft => ShiftRowRight,
+            ShiftRowRight => ShiftRowLeft,
+            ShiftColumnUp => ShiftColumnDown,
+            ShiftColumnDown => ShiftColumnUp,
             ActiveRotateCW => ActiveRotateCCW,
             ActiveRotateCCW => ActiveRotateCW,
             _ => *self,
         }
     }
+    // Direction, as (row,column) increments, in which tiles are moved by this action
+    pub fn direction(&self) -> Option<(isize, isize)> {
+        use PuzzleAction::*;
+        match self {
+            MoveLeft | ShiftRowLeft => Some((0, -1)),
+            MoveRight | ShiftRowRight => Some((0, 1)),
+            MoveUp | ShiftColumnUp => Some((1, 0)),
+            MoveDown | ShiftColumnDown => Some((-1, 0)),
+            _ => None,
+        }
+    }
+    // Direction in which the tile is moved by this action on a hexagonal grid
+    pub fn hex_direction(&self) -> Option<HexDirection> {
+        use PuzzleAction::*;
+        match self {
+            MoveLeft => Some(HexDirection::West),
+            MoveRight => Some(HexDirection::East),
+            MoveUpLeft => Some(HexDirection::NorthWest),
+            MoveUpRight => Some(HexDirection::NorthEast),
+            MoveDownLeft => Some(HexDirection::SouthWest),
+            MoveDownRight => Some(HexDirection::SouthEast),
+            _ => None,
+        }
+    }
+}
+
+// What an action changed on the puzzle, returned by Puzzle::apply_action
+#[derive(Debug, Clone)]
+pub enum ActionEffect {
+    // Nothing changed, like a move of the active tile against the border
+    None,
+    // The active tile or hole changed
+    Selection,
+    // The action can't be done, like sliding no tile or flipping a fixed one
+    Invalid,
+    // Tiles slid by a move or a shift, as (entity, source, destination)
+    Moves {
+        action: PuzzleAction,
+        moves: Vec<(Option<Entity>, Coord, Coord)>,
+    },
+    // The active tile was flipped, local_flip being the flip on its own axis
+    Flip {
+        start: Tile,
+        end: Tile,
+        local_flip: PuzzleAction,
+    },
+    // The active tile was rotated
+    Rotation {
+        start: Tile,
+        end: Tile,
+    },
+}
+
+impl ActionEffect {
+    // True if the puzzle changed
+    pub fn changed(&self) -> bool {
+        !matches!(self, ActionEffect::None | ActionEffect::Invalid)
+    }
 }
 
 pub fn handle_puzzle_action_events(
+    mut commands: Commands,
     mut events: EventReader<PuzzleAction>,
+    mut puzzle_action_events: EventReader<PuzzleActionEvent>,
+    focused: Query<Entity, With<FocusedPuzzle>>,
     mut puzzle: Query<(&mut Puzzle, &PuzzleAssets)>,
+    children: Query<&Children>,
     mut transforms: Query<&mut Transform>,
     mut action_tip_visibility: Query<&mut Visibility, With<ActionTip>>,
     mut tile_animations: Query<&mut TileAnimation>,
     mut outlines: Query<&mut OutlineVolume>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut solved_events: EventWriter<PuzzleSolvedEvent>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
 ) {
     use PuzzleAction::*;
-    for event in events.read() {
-        if let Ok((mut puzzle, puzzle_assets)) = puzzle.get_single_mut() {
-            if !puzzle.is_solved {
-                match event {
-                    NoAction => (),
-                    MoveLeft | MoveRight | MoveUp | MoveDown | MoveActive => {
-                        let (entity, destination, source) = puzzle.apply_move_event(*event);
-                        if let Some(entity) = entity {
-                            let start_translation =
-                                tile_translation_from_position(source, puzzle.size());
-                            let end_translation =
-                                tile_translation_from_position(destination, puzzle.size());
-                            let tween = Tween::new(
-                                EaseFunction::QuadraticInOut,
-                                Duration::from_millis(ACTION_ANIMATION_DURATION),
-                                TransformPositionLens {
-                                    start: start_translation,
-                                    end: end_translation,
+    // PuzzleAction events go to the focused puzzle
+    let focused = focused.get_single().ok();
+    let events = events
+        .read()
+        .filter_map(|action| {
+            Some(PuzzleActionEvent {
+                puzzle: focused?,
+                action: *action,
+            })
+        })
+        .chain(puzzle_action_events.read().copied())
+        .collect::<Vec<_>>();
+    let mut solved_any = false;
+    for PuzzleActionEvent {
+        puzzle: puzzle_entity,
+        action,
+    } in events
+    {
+        let Ok((mut puzzle, puzzle_assets)) = puzzle.get_mut(puzzle_entity) else {
+            continue;
+        };
+        if puzzle.is_solved {
+            continue;
+        }
+        let duration = settings.action_animation_duration();
+        match puzzle.apply_action(action) {
+            ActionEffect::None | ActionEffect::Selection => (),
+            ActionEffect::Invalid => {
+                sound_effects.send(SoundEffect::Invalid);
+            }
+            ActionEffect::Moves { action, moves } => {
+                let size = puzzle.size();
+                for (entity, source, destination) in moves {
+                    let Some(entity) = entity else {
+                        continue;
+                    };
+                    // On a wrapping grid, the tile may come from the opposite edge
+                    let wrap_direction = (!dist_of_1(source, destination, size, false))
+                        .then(|| action.direction())
+                        .flatten();
+                    let tween = tile_slide_tween(
+                        source,
+                        destination,
+                        wrap_direction,
+                        size,
+                        puzzle.shape,
+                        duration,
+                    );
+                    let mut tile_animation = tile_animations.get_mut(entity).expect("Oops");
+                    tile_animation.push_transform_tween(tween);
+                }
+                hide_action_tip(&puzzle, &children, &mut action_tip_visibility);
+                sound_effects.send(SoundEffect::Slide);
+                let active_hole = puzzle.active_hole;
+                if let Some(hole_entity) = puzzle.hole_entities.get(active_hole) {
+                    let mut transform = transforms
+                        .get_mut(*hole_entity)
+                        .expect("No Transform for the hole entity");
+                    transform.translation = tile_translation_from_position(
+                        puzzle.holes[active_hole],
+                        size,
+                        puzzle.shape,
+                    );
+                    // The outline of a jigsaw hole follows the edges of its position
+                    commands
+                        .entity(*hole_entity)
+                        .insert(puzzle_assets.tile_meshes.get(
+                            puzzle.holes[active_hole],
+                            false,
+                            false,
+                        ));
+                }
+            }
+            ActionEffect::Flip {
+                start,
+                end,
+                local_flip,
+            } => {
+                sound_effects.send(SoundEffect::Flip);
+                if let Some(entity) = end.entity {
+                    let mut tile_animation = tile_animations.get_mut(entity).expect("Oops");
+                    if end.shape == PuzzleShape::Hex {
+                        let screen_axis = if local_flip == ActiveFlipX {
+                            Vec2::X
+                        } else {
+                            Vec2::Y
+                        };
+                        let mesh_tween = Tween::new(
+                            EaseFunction::QuadraticInOut,
+                            duration,
+                            MeshFlippingLens::new_hex_flip(
+                                end.clone(),
+                                screen_axis,
+                                start.rotation,
+                                end.rotation,
+                            ),
+                        );
+                        let transform_tween = Tween::new(
+                            EaseFunction::QuadraticInOut,
+                            duration,
+                            RotationSnapLens {
+                                start: start.compute_rotation(),
+                                end: end.compute_rotation(),
+                            },
+                        );
+                        tile_animation.push_tweens(transform_tween, mesh_tween);
+                    } else {
+                        // The tile is squashed on its local axis, its shared mesh
+                        // is swapped for the flipped one, then it is unsquashed.
+                        // It is never fully squashed, to keep an invertible transform.
+                        let half_duration = duration / 2;
+                        let scale = puzzle_assets.tile_scale;
+                        let squashed = match local_flip {
+                            ActiveFlipX => scale * Vec3::new(0.01, 1., 1.),
+                            ActiveFlipY => scale * Vec3::new(1., 0.01, 1.),
+                            _ => panic!(),
+                        };
+                        tile_animation.push_transform_tween(Tween::new(
+                            EaseFunction::QuadraticIn,
+                            half_duration,
+                            TransformScaleLens {
+                                start: scale,
+                                end: squashed,
+                            },
+                        ));
+                        tile_animation.push_transform_tween_with_mesh(
+                            Tween::new(
+                                EaseFunction::QuadraticOut,
+                                half_duration,
+                                TransformScaleLens {
+                                    start: squashed,
+                                    end: scale,
                                 },
-                            );
-                            // This action count should be on puzzle methods
-                            puzzle.actions_count += 1;
-                            let mut tile_animation = tile_animations.get_mut(entity).expect("Oops");
-                            tile_animation.push_transform_tween(tween);
-                            for mut visibility in action_tip_visibility.iter_mut() {
-                                *visibility = Visibility::Hidden;
-                            }
-                        }
-                        let hole = puzzle.hole;
-                        let size = puzzle.size();
-                        if let Some(hole_entity) = puzzle.hole_entity {
-                            let mut transform = transforms
-                                .get_mut(hole_entity)
-                                .expect("No Transform for the hole entity");
-                            transform.translation = tile_translation_from_position(hole, size)
-                        }
-                    }
-                    MoveActiveLeft | MoveActiveRight | MoveActiveUp | MoveActiveDown => {
-                        puzzle.apply_move_active_event(*event);
-                    }
-                    SetActive(entity) => {
-                        puzzle.set_active_entity(entity);
-                    }
-                    ActiveFlipX | ActiveFlipY => {
-                        // TODO: this effective flip and event conversion should be a puzzle method
-                        // Only handling here a returned tile_entity and returned effective event
-                        // TODO: This action count should be on puzzle methods
-                        if puzzle.active != puzzle.hole {
-                            puzzle.actions_count += 1;
-                        }
-                        if let Some(tile) = puzzle.get_active_tile_mut() {
-                            // Convert the X/Y user axis to the local tile axis, based on tile rotation
-                            let local_event = {
-                                use CwRotation::*;
-                                match tile.rotation {
-                                    R0 | R180 => event,
-                                    R90 | R270 => match event {
-                                        ActiveFlipX => &ActiveFlipY,
-                                        ActiveFlipY => &ActiveFlipX,
-                                        _ => panic!(),
-                                    },
-                                }
-                            };
-                            match local_event {
-                                ActiveFlipX => tile.flip_x(),
-                                ActiveFlipY => tile.flip_y(),
-                                _ => panic!(),
-                            }
-                            if let Some(entity) = tile.entity {
-                                let tween = Tween::new(
-                                    EaseFunction::QuadraticInOut,
-                                    Duration::from_millis(ACTION_ANIMATION_DURATION),
-                                    match local_event {
-                                        ActiveFlipX => MeshFlippingLens::new_flip_x(tile.clone()),
-                                        ActiveFlipY => MeshFlippingLens::new_flip_y(tile.clone()),
-                                        _ => panic!(),
-                                    },
-                                );
-                                let mut tile_animation =
-                                    tile_animations.get_mut(entity).expect("Oops");
-                                tile_animation.push_mesh_tween(tween);
-                            }
-                        }
-                    }
-                    ActiveRotateCW | ActiveRotateCCW => {
-                        // TODO: this rotationd event should be a puzzle method
-                        // Only handling here a returned tile_entity
-                        // TODO: This action count should be on puzzle methods
-                        if puzzle.active != puzzle.hole {
-                            puzzle.actions_count += 1;
-                        }
-                        if let Some(tile) = puzzle.get_active_tile_mut() {
-                            let start_rotation = tile.compute_rotation();
-                            match event {
-                                ActiveRotateCW => tile.rotate_cw(),
-                                ActiveRotateCCW => tile.rotate_ccw(),
-                                _ => panic!(),
-                            }
-                            if let Some(entity) = tile.entity {
-                                let end_rotation = tile.compute_rotation();
-                                let tween = Tween::new(
-                                    EaseFunction::QuadraticInOut,
-                                    Duration::from_millis(ACTION_ANIMATION_DURATION),
-                                    TransformRotationLens {
-                                        start: start_rotation,
-                                        end: end_rotation,
-                                    },
-                                );
-                                let mut tile_animation =
-                                    tile_animations.get_mut(entity).expect("Oops");
-                                tile_animation.push_transform_tween(tween);
-                            }
-                        }
+                            ),
+                            puzzle_assets.tile_meshes.tile_mesh(&end),
+                        );
                     }
                 }
-                puzzle.compute_solved();
-                if puzzle.is_solved {
-                    println!("SOLVED in {} actions", puzzle.actions_count);
-                    next_state.set(GameState::PuzzleSolved);
+            }
+            ActionEffect::Rotation { start, end } => {
+                sound_effects.send(SoundEffect::Rotate);
+                if let Some(entity) = end.entity {
+                    let tween = Tween::new(
+                        EaseFunction::QuadraticInOut,
+                        duration,
+                        TransformRotationLens {
+                            start: start.compute_rotation(),
+                            end: end.compute_rotation(),
+                        },
+                    );
+                    let mut tile_animation = tile_animations.get_mut(entity).expect("Oops");
+                    tile_animation.push_transform_tween(tween);
                 }
-                puzzle.show_outlines(&mut outlines, puzzle_assets);
             }
         }
+        if puzzle.is_solved {
+            info!("SOLVED in {} actions", puzzle.actions_count);
+            sound_effects.send(SoundEffect::Solved);
+            solved_events.send(PuzzleSolvedEvent {
+                puzzle: puzzle_entity,
+                actions_count: puzzle.actions_count,
+            });
+            solved_any = true;
+        }
+        puzzle.show_outlines(&mut outlines, puzzle_assets, &theme);
+    }
+    // Puzzles shown together are done once they are all solved
+    if solved_any && puzzle.iter().all(|(puzzle, _)| puzzle.is_solved) {
+        next_state.set(GameState::PuzzleSolved);
+    }
+}
+
+// Hides the action tip of a puzzle, until the pointer moves over one of its tiles again
+fn hide_action_tip(
+    puzzle: &Puzzle,
+    children: &Query<&Children>,
+    action_tip_visibility: &mut Query<&mut Visibility, With<ActionTip>>,
+) {
+    for entity in puzzle
+        .action_tip_entity
+        .into_iter()
+        .flat_map(|entity| children.iter_descendants(entity))
+    {
+        if let Ok(mut visibility) = action_tip_visibility.get_mut(entity) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+// Puzzles that can be played with the pointer. The boards of a versus race are only
+// played with the keys of their player, the pointer can't play for either of them.
+pub type PointerPuzzles<'w, 's> = Query<'w, 's, (), (With<Puzzle>, Without<VersusPlayer>)>;
+
+// Puzzle of a tile or hole entity, if it can be played with the pointer
+pub fn puzzle_of(
+    entity: Entity,
+    parents: &Query<&Parent>,
+    puzzles: &PointerPuzzles,
+) -> Option<Entity> {
+    parents
+        .iter_ancestors(entity)
+        .find(|ancestor| puzzles.contains(*ancestor))
+}
+
+// Moves the focus to the given puzzle
+pub fn focus_puzzle(
+    commands: &mut Commands,
+    puzzle: Entity,
+    focused: &Query<Entity, With<FocusedPuzzle>>,
+) {
+    for entity in focused.iter().filter(|entity| *entity != puzzle) {
+        commands.entity(entity).remove::<FocusedPuzzle>();
+    }
+    if !focused.contains(puzzle) {
+        commands.entity(puzzle).insert(FocusedPuzzle);
+    }
+}
+
+// Sort key of puzzles from left to right, see PuzzleSlot
+pub fn puzzle_order(entity: Entity, slot: Option<&PuzzleSlot>) -> (usize, Entity) {
+    (slot.map_or(usize::MAX, |slot| slot.0), entity)
+}
+
+// Focuses the first puzzle when none is focused, like when the focused one is despawned
+pub fn keep_puzzle_focus(
+    mut commands: Commands,
+    puzzles: Query<(Entity, Option<&PuzzleSlot>), With<Puzzle>>,
+    focused: Query<Entity, With<FocusedPuzzle>>,
+) {
+    if !focused.is_empty() {
+        return;
+    }
+    if let Some((entity, _)) = puzzles
+        .iter()
+        .min_by_key(|(entity, slot)| puzzle_order(*entity, *slot))
+    {
+        commands.entity(entity).insert(FocusedPuzzle);
     }
 }
 
 pub fn show_full_puzzle(
     mut commands: Commands,
-    // Restrict to Changed Puzzle, so that full size animation only happens once.
-    // In particular we don't want to re-animate on called after a Menu::Show.
-    puzzle: Query<(&Puzzle, &PuzzleAssets), Changed<Puzzle>>,
+    mut solved_events: EventReader<PuzzleSolvedEvent>,
+    puzzle: Query<(&Puzzle, &PuzzleAssets)>,
+    children: Query<&Children>,
     mut action_tip_visibility: Query<&mut Visibility, With<ActionTip>>,
     mut tile_animations: Query<&mut TileAnimation>,
     mut outlines: Query<&mut OutlineVolume>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    // Hide all action tips, and all outlines
-    for mut visibility in action_tip_visibility.iter_mut() {
-        *visibility = Visibility::Hidden;
-    }
-    for mut outline in outlines.iter_mut() {
-        outline.visible = false;
-    }
-    // Animate all puzzle to reach their full size
+    // Animate each solved puzzle to reach its full size, once, when it is solved.
     // Change the 'hole' tile to contain its image part.
-    if let Ok((puzzle, puzzle_assets)) = puzzle.get_single() {
+    for event in solved_events.read() {
+        let Ok((puzzle, puzzle_assets)) = puzzle.get(event.puzzle) else {
+            continue;
+        };
+        // Hide the action tip and all outlines
+        hide_action_tip(puzzle, &children, &mut action_tip_visibility);
+        let entities = puzzle
+            .tiles
+            .iter()
+            .filter_map(|tile| tile.as_ref().and_then(|tile| tile.entity))
+            .chain(puzzle.hole_entities.iter().copied());
+        for entity in entities {
+            if let Ok(mut outline) = outlines.get_mut(entity) {
+                outline.visible = false;
+            }
+        }
         for tile in puzzle.tiles.iter().filter_map(|tile| tile.as_ref()) {
             if let Some(entity) = tile.entity {
                 let tween = Tween::new(
@@ -515,18 +1266,14 @@ pub fn show_full_puzzle(
                 tile_animation.push_transform_tween(tween);
             }
         }
-        if let Some(entity) = puzzle.hole_entity {
-            let final_hole_mesh =
-                meshes.add(compute_tile_mesh(puzzle.size(), puzzle.hole, false, false));
-            let final_hole_material = materials.add(StandardMaterial {
-                base_color_texture: Some(puzzle.image.clone()),
-                unlit: true,
-                ..default()
-            });
+        for (entity, hole) in puzzle.hole_entities.iter().zip(puzzle.holes.iter()) {
+            let entity = *entity;
+            // An expert puzzle ends on its back image, shown upright by a Flip X
+            let final_hole_mesh = puzzle_assets.tile_meshes.get(*hole, puzzle.expert, false);
             commands
                 .entity(entity)
                 .insert(final_hole_mesh)
-                .insert(final_hole_material);
+                .insert(puzzle_assets.tile_material.clone());
             let tween = Tween::new(
                 EaseFunction::QuadraticInOut,
                 Duration::from_millis(500),
@@ -541,20 +1288,39 @@ pub fn show_full_puzzle(
     }
 }
 
+// Queue of tweens played one after the other, each with an optional mesh
+// that replaces the entity mesh when it starts.
 #[derive(Component)]
 pub struct TileAnimation {
-    queue: VecDeque<(BoxedTweenable<Transform>, BoxedTweenable<Mesh>)>,
+    queue: VecDeque<(
+        BoxedTweenable<Transform>,
+        BoxedTweenable<Mesh>,
+        Option<Handle<Mesh>>,
+    )>,
 }
 impl TileAnimation {
     pub fn push_transform_tween(&mut self, tween: impl Tweenable<Transform> + 'static) {
         let duration = tween.duration();
         self.queue
-            .push_back((Box::new(tween), Box::new(Delay::new(duration))))
+            .push_back((Box::new(tween), Box::new(Delay::new(duration)), None))
     }
-    pub fn push_mesh_tween(&mut self, tween: impl Tweenable<Mesh> + 'static) {
+    pub fn push_transform_tween_with_mesh(
+        &mut self,
+        tween: impl Tweenable<Transform> + 'static,
+        mesh: Handle<Mesh>,
+    ) {
         let duration = tween.duration();
         self.queue
-            .push_back((Box::new(Delay::new(duration)), Box::new(tween)))
+            .push_back((Box::new(tween), Box::new(Delay::new(duration)), Some(mesh)))
+    }
+    // Both tweens are played at the same time, they must have the same duration
+    pub fn push_tweens(
+        &mut self,
+        transform_tween: impl Tweenable<Transform> + 'static,
+        mesh_tween: impl Tweenable<Mesh> + 'static,
+    ) {
+        self.queue
+            .push_back((Box::new(transform_tween), Box::new(mesh_tween), None))
     }
 }
 #[derive(Bundle)]
@@ -580,14 +1346,20 @@ pub fn tile_animation(
         &mut TileAnimation,
         &mut Animator<Transform>,
         &mut AssetAnimator<Mesh>,
+        &mut Handle<Mesh>,
     )>,
 ) {
-    for (mut tile_animation, mut transform_animator, mut mesh_animator) in animations.iter_mut() {
+    for (mut tile_animation, mut transform_animator, mut mesh_animator, mut mesh) in
+        animations.iter_mut()
+    {
         if !tile_animation.queue.is_empty()
             && transform_animator.tweenable().progress() >= 1.0
             && mesh_animator.tweenable().progress() >= 1.0
         {
-            let (transform_tween, mesh_tween) = tile_animation.queue.pop_front().unwrap();
+            let (transform_tween, mesh_tween, new_mesh) = tile_animation.queue.pop_front().unwrap();
+            if let Some(new_mesh) = new_mesh {
+                *mesh = new_mesh;
+            }
             transform_animator.set_tweenable(Sequence::new([transform_tween]));
             mesh_animator.set_tweenable(Sequence::new([mesh_tween]));
         }
@@ -596,64 +1368,132 @@ pub fn tile_animation(
 
 pub fn tile_on_click(
     event: Listener<Pointer<Click>>,
-    mut puzzle_action_events: EventWriter<PuzzleAction>,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
     transforms: Query<&GlobalTransform>,
+    puzzle: Query<&Puzzle>,
+    parents: Query<&Parent>,
+    puzzles: PointerPuzzles,
 ) {
+    let Some(puzzle_entity) = puzzle_of(event.target, &parents, &puzzles) else {
+        return;
+    };
     let tile_global_transform = transforms
         .get(event.target)
         .expect("No Transform for Tile")
         .compute_transform();
-    // local_translation will have a [-0.5,0.5] range for x and y
-    let local_translation = (event.hit.position.unwrap() - tile_global_transform.translation)
-        / tile_global_transform.scale;
-    puzzle_action_events.send(action_from_tip_local(
-        local_translation.x,
-        local_translation.y,
-    ));
+    // local_translation will have a [-0.5,0.5] range for x and y,
+    // once clamped as jigsaw tabs stick out of the tile square
+    let local_translation = ((event.hit.position.unwrap() - tile_global_transform.translation)
+        / tile_global_transform.scale)
+        .clamp(Vec3::splat(-0.5), Vec3::splat(0.5));
+    puzzle_action_events.send(PuzzleActionEvent {
+        puzzle: puzzle_entity,
+        action: action_from_tip_local(
+            local_translation.x,
+            local_translation.y,
+            puzzle.get(puzzle_entity).expect("Oops").shape,
+        ),
+    });
+}
+
+pub fn hole_on_click(
+    event: Listener<Pointer<Click>>,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
+    parents: Query<&Parent>,
+    puzzles: PointerPuzzles,
+) {
+    if let Some(puzzle) = puzzle_of(event.target, &parents, &puzzles) {
+        puzzle_action_events.send(PuzzleActionEvent {
+            puzzle,
+            action: PuzzleAction::SetActive(event.target),
+        });
+    }
 }
 
 pub fn tile_on_moving_over(
     event: Listener<Pointer<Move>>,
-    mut puzzle_action_events: EventWriter<PuzzleAction>,
+    mut commands: Commands,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
     mut transforms: Query<&mut Transform>,
     global_transforms: Query<&GlobalTransform>,
-    mut action_tip: Query<(Entity, &mut Visibility), With<ActionTip>>,
+    mut action_tip: Query<&mut Visibility, With<ActionTip>>,
     puzzle: Query<(&Puzzle, &PuzzleAssets)>,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    puzzles: PointerPuzzles,
+    focused: Query<Entity, With<FocusedPuzzle>>,
     mut action_tip_icon_material: Query<&mut Handle<StandardMaterial>, With<ActionTipIcon>>,
 ) {
-    puzzle_action_events.send(PuzzleAction::SetActive(event.target));
+    // The puzzle under the pointer gets the focus
+    let Some(puzzle_entity) = puzzle_of(event.target, &parents, &puzzles) else {
+        return;
+    };
+    focus_puzzle(&mut commands, puzzle_entity, &focused);
+    let (puzzle, puzzle_assets) = puzzle.get(puzzle_entity).expect("Oops");
+    // Solved puzzles show their full image, without action tip
+    if puzzle.is_solved {
+        return;
+    }
+    puzzle_action_events.send(PuzzleActionEvent {
+        puzzle: puzzle_entity,
+        action: PuzzleAction::SetActive(event.target),
+    });
+    let action_tip_entities = puzzle
+        .action_tip_entity
+        .into_iter()
+        .flat_map(|entity| children.iter_descendants(entity))
+        .collect::<Vec<_>>();
     let tile_transform = *transforms.get(event.target).expect("No transform for tile");
-    if let Ok((entity, mut action_tip)) = action_tip.get_single_mut() {
-        *action_tip = Visibility::Inherited;
-        let mut action_transform = transforms
-            .get_mut(entity)
-            .expect("No transform for ActionTip");
-        action_transform.translation = tile_transform.translation;
+    for entity in action_tip_entities.iter() {
+        if let Ok(mut visibility) = action_tip.get_mut(*entity) {
+            *visibility = Visibility::Inherited;
+            let mut action_transform = transforms
+                .get_mut(*entity)
+                .expect("No transform for ActionTip");
+            action_transform.translation = tile_transform.translation;
+        }
     }
     // local_translation will have a [-0.5,0.5] range for x and y
     let tile_global_transform = global_transforms
         .get(event.target)
         .expect("No Transform for Tile")
         .compute_transform();
-    // local_translation will have a [-0.5,0.5] range for x and y
-    let local_translation = (event.hit.position.unwrap() - tile_global_transform.translation)
-        / tile_global_transform.scale;
-    let (puzzle, puzzle_assets) = puzzle.single();
-    let action = puzzle.convert_action(action_from_tip_local(
-        local_translation.x,
-        local_translation.y,
-    ));
-    let mut action_tip_icon_material = action_tip_icon_material.single_mut();
-    *action_tip_icon_material = puzzle_assets
+    // local_translation will have a [-0.5,0.5] range for x and y,
+    // once clamped as jigsaw tabs stick out of the tile square
+    let local_translation = ((event.hit.position.unwrap() - tile_global_transform.translation)
+        / tile_global_transform.scale)
+        .clamp(Vec3::splat(-0.5), Vec3::splat(0.5));
+    let is_wall = puzzle
+        .tiles
+        .iter()
+        .flatten()
+        .any(|tile| tile.fixed && tile.entity == Some(event.target));
+    let action = if is_wall {
+        PuzzleAction::NoAction
+    } else {
+        puzzle.convert_action(action_from_tip_local(
+            local_translation.x,
+            local_translation.y,
+            puzzle.shape,
+        ))
+    };
+    let icon_material = puzzle_assets
         .action_tip_materials
         .get(&action)
-        .unwrap_or_else(|| panic!("No icon for action {action:?}"))
-        .clone();
+        .unwrap_or_else(|| panic!("No icon for action {action:?}"));
+    for entity in action_tip_entities {
+        if let Ok(mut action_tip_icon_material) = action_tip_icon_material.get_mut(entity) {
+            *action_tip_icon_material = icon_material.clone();
+        }
+    }
 }
 
-pub fn action_from_tip_local(x: f32, y: f32) -> PuzzleAction {
+pub fn action_from_tip_local(x: f32, y: f32, shape: PuzzleShape) -> PuzzleAction {
     assert!((-0.5..=0.5).contains(&x), "Unexpected x:{x}");
     assert!((-0.5..=0.5).contains(&y), "Unexpected y:{y}");
+    if shape == PuzzleShape::Hex {
+        return action_from_hex_tip_local(x, y);
+    }
     match ((x < -0.3, x > 0.3), (y < -0.3, y > 0.3)) {
         ((true, false), (true, false)) => PuzzleAction::ActiveRotateCCW, // Left Down
         ((true, false), (false, false)) => PuzzleAction::ActiveFlipX,    // Left Middle
@@ -673,8 +1513,10 @@ const PUZZLE_SOLVED_EXIT: [KeyCode; 3] = [KeyCode::Enter, KeyCode::Space, KeyCod
 pub fn puzzle_solving_interaction(
     mut puzzle_solution: Query<&mut Visibility, (With<PuzzleSolution>, Without<PuzzleTiles>)>,
     mut puzzle_tiles: Query<&mut Visibility, With<PuzzleTiles>>,
-    mut puzzle: Query<(&mut Puzzle, &PuzzleAssets)>,
+    mut puzzle: Query<(Entity, &mut Puzzle, &PuzzleAssets), With<FocusedPuzzle>>,
+    children: Query<&Children>,
     mut outlines: Query<&mut OutlineVolume>,
+    theme: Res<Theme>,
     input: Res<ButtonInput<KeyCode>>,
     mouse_button: Res<ButtonInput<MouseButton>>,
     mut puzzle_move_events: EventWriter<PuzzleAction>,
@@ -692,6 +1534,22 @@ pub fn puzzle_solving_interaction(
     if input.just_pressed(KeyCode::KeyE) {
         puzzle_move_events.send(PuzzleAction::ActiveRotateCW);
     }
+    if input.just_pressed(KeyCode::Tab) {
+        puzzle_move_events.send(PuzzleAction::NextHole);
+    }
+    // Diagonal moves of hexagonal puzzles, around the J key
+    if input.just_pressed(KeyCode::KeyU) {
+        puzzle_move_events.send(PuzzleAction::MoveUpLeft);
+    }
+    if input.just_pressed(KeyCode::KeyI) {
+        puzzle_move_events.send(PuzzleAction::MoveUpRight);
+    }
+    if input.just_pressed(KeyCode::KeyN) {
+        puzzle_move_events.send(PuzzleAction::MoveDownLeft);
+    }
+    if input.just_pressed(KeyCode::KeyM) {
+        puzzle_move_events.send(PuzzleAction::MoveDownRight);
+    }
     if input.just_pressed(KeyCode::ArrowRight) {
         if input.pressed(KeyCode::ShiftLeft) {
             puzzle_move_events.send(PuzzleAction::MoveActiveRight);
@@ -723,13 +1581,15 @@ pub fn puzzle_solving_interaction(
     // Handle display of the solution overlay pressing/releasing a key
     // Beware that some kind of puzzle don't have a solution that can be shown
     if input.just_pressed(KeyCode::ControlLeft) {
-        if let Ok((puzzle, _)) = puzzle.get_single() {
+        if let Ok((entity, puzzle, _)) = puzzle.get_single() {
             if !puzzle.is_solved {
-                for mut solution in puzzle_solution.iter_mut() {
-                    *solution = Visibility::Visible;
-                }
-                for mut tiles in puzzle_tiles.iter_mut() {
-                    *tiles = Visibility::Hidden;
+                for child in children.get(entity).into_iter().flatten() {
+                    if let Ok(mut solution) = puzzle_solution.get_mut(*child) {
+                        *solution = Visibility::Visible;
+                    }
+                    if let Ok(mut tiles) = puzzle_tiles.get_mut(*child) {
+                        *tiles = Visibility::Hidden;
+                    }
                 }
             }
         }
@@ -743,15 +1603,15 @@ pub fn puzzle_solving_interaction(
         }
     }
     if input.just_pressed(KeyCode::Space) {
-        if let Ok((mut puzzle, puzzle_assets)) = puzzle.get_single_mut() {
+        if let Ok((_, mut puzzle, puzzle_assets)) = puzzle.get_single_mut() {
             puzzle.show_errors = true;
-            puzzle.show_outlines(&mut outlines, puzzle_assets);
+            puzzle.show_outlines(&mut outlines, puzzle_assets, &theme);
         }
     }
     if input.just_released(KeyCode::Space) {
-        if let Ok((mut puzzle, puzzle_assets)) = puzzle.get_single_mut() {
+        if let Ok((_, mut puzzle, puzzle_assets)) = puzzle.get_single_mut() {
             puzzle.show_errors = false;
-            puzzle.show_outlines(&mut outlines, puzzle_assets);
+            puzzle.show_outlines(&mut outlines, puzzle_assets, &theme);
         }
     }
     if input.just_pressed(KeyCode::Escape) || mouse_button.just_pressed(MouseButton::Right) {
@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+use crate::prelude::*;
+
+// Move notation of action sequences, tokens being separated by spaces:
+//   U D L R        slides, or shifts of Loopover puzzles
+//   UL UR DL DR    diagonal slides of hexagonal puzzles
+//   a@<ROW>,<COL>  selection of the tile or hole at a position, rows counted from the top
+//                  and columns from the left, both starting at 1
+//   X Y            flips on the screen axes
+//   + -            clock-wise and counter clock-wise rotations
+// For example: U L a@2,3 + X
+pub fn format_moves(actions: &[PuzzleAction], size: Coord) -> String {
+    use PuzzleAction::*;
+    actions
+        .iter()
+        .filter_map(|action| {
+            let token = match action {
+                MoveUp | ShiftColumnUp => "U",
+                MoveDown | ShiftColumnDown => "D",
+                MoveLeft | ShiftRowLeft => "L",
+                MoveRight | ShiftRowRight => "R",
+                MoveUpLeft => "UL",
+                MoveUpRight => "UR",
+                MoveDownLeft => "DL",
+                MoveDownRight => "DR",
+                ActiveFlipX => "X",
+                ActiveFlipY => "Y",
+                ActiveRotateCW => "+",
+                ActiveRotateCCW => "-",
+                SetActivePosition((row, column)) => {
+                    return Some(format!("a@{},{}", size.0 - row, column + 1));
+                }
+                // Relative selections and entities are only kept as positions
+                _ => return None,
+            };
+            Some(token.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn parse_moves(text: &str, size: Coord) -> Result<Vec<PuzzleAction>, String> {
+    use PuzzleAction::*;
+    text.split_whitespace()
+        .map(|token| {
+            Ok(match token {
+                "U" => MoveUp,
+                "D" => MoveDown,
+                "L" => MoveLeft,
+                "R" => MoveRight,
+                "UL" => MoveUpLeft,
+                "UR" => MoveUpRight,
+                "DL" => MoveDownLeft,
+                "DR" => MoveDownRight,
+                "X" => ActiveFlipX,
+                "Y" => ActiveFlipY,
+                "+" => ActiveRotateCW,
+                "-" => ActiveRotateCCW,
+                _ => {
+                    let position = token
+                        .strip_prefix("a@")
+                        .and_then(|position| position.split_once(','))
+                        .and_then(|(row, column)| {
+                            Some((row.parse::<usize>().ok()?, column.parse::<usize>().ok()?))
+                        })
+                        .filter(|(row, column)| {
+                            (1..=size.0).contains(row) && (1..=size.1).contains(column)
+                        })
+                        .ok_or_else(|| format!("Invalid move {}", token))?;
+                    SetActivePosition((size.0 - position.0, position.1 - 1))
+                }
+            })
+        })
+        .collect()
+}
+
+// Actions pasted by the player, applied one animation after the other
+#[derive(Resource, Default)]
+pub struct ActionReplay {
+    // Puzzle the actions were pasted on, they are dropped when another puzzle starts
+    puzzle: Option<Entity>,
+    actions: VecDeque<PuzzleAction>,
+    // Puzzle being pasted on, until the clipboard text is received
+    pasting: Option<(Entity, Mutex<Receiver<Result<String, String>>>)>,
+}
+
+// C copies the moves of the player to the clipboard, V applies the moves of the clipboard,
+// both on the focused puzzle
+pub fn copy_paste_moves(
+    input: Res<ButtonInput<KeyCode>>,
+    focused: Query<Entity, With<FocusedPuzzle>>,
+    puzzles: Query<&Puzzle>,
+    mut replay: ResMut<ActionReplay>,
+) {
+    if let Some((entity, puzzle)) = focused
+        .get_single()
+        .ok()
+        .and_then(|entity| Some((entity, puzzles.get(entity).ok()?)))
+    {
+        if input.just_pressed(KeyCode::KeyC) {
+            let moves = format_moves(&puzzle.history, puzzle.size());
+            match set_clipboard_text(&moves) {
+                Ok(()) => info!("Copied moves: {}", moves),
+                Err(err) => warn!("Can't copy moves: {}", err),
+            }
+        }
+        if input.just_pressed(KeyCode::KeyV) {
+            replay.pasting = Some((entity, Mutex::new(get_clipboard_text())));
+        }
+    }
+    // The web clipboard gives its text a few frames later
+    let Some((entity, text)) = &replay.pasting else {
+        return;
+    };
+    let text = match text.lock().expect("Oops").try_recv() {
+        Ok(text) => text,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => Err("The clipboard text was lost".to_string()),
+    };
+    let entity = *entity;
+    replay.pasting = None;
+    let Ok(puzzle) = puzzles.get(entity) else {
+        return;
+    };
+    match text.and_then(|text| parse_moves(&text, puzzle.size())) {
+        Ok(actions) => {
+            if replay.puzzle != Some(entity) {
+                replay.actions.clear();
+                replay.puzzle = Some(entity);
+            }
+            replay.actions.extend(actions);
+        }
+        Err(err) => warn!("Can't paste moves: {}", err),
+    }
+}
+
+// Sends the next pasted action once the previous one has been animated.
+// Selections are not animated, they are sent along with the following action.
+pub fn replay_actions(
+    mut replay: ResMut<ActionReplay>,
+    puzzle: Query<(), With<Puzzle>>,
+    animations: Query<(&TileAnimation, &Animator<Transform>)>,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
+) {
+    if replay.actions.is_empty() {
+        return;
+    }
+    let Some(puzzle_entity) = replay.puzzle.filter(|entity| puzzle.contains(*entity)) else {
+        replay.actions.clear();
+        return;
+    };
+    let animating = animations.iter().any(|(tile_animation, animator)| {
+        !tile_animation.queue.is_empty() || animator.tweenable().progress() < 1.0
+    });
+    if animating {
+        return;
+    }
+    while let Some(action) = replay.actions.pop_front() {
+        puzzle_action_events.send(PuzzleActionEvent {
+            puzzle: puzzle_entity,
+            action,
+        });
+        if !matches!(action, PuzzleAction::SetActivePosition(_)) {
+            break;
+        }
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn set_clipboard_text(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|err| err.to_string())
+}
+// Text of the clipboard, sent right away
+#[cfg(not(target_family = "wasm"))]
+fn get_clipboard_text() -> Receiver<Result<String, String>> {
+    let (sender, receiver) = channel();
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| err.to_string());
+    sender.send(text).ok();
+    receiver
+}
+// The browser clipboard is asynchronous, and asks the player for the permission to read it
+#[cfg(target_family = "wasm")]
+fn web_clipboard() -> Result<web_sys::Clipboard, String> {
+    web_sys::window()
+        .map(|window| window.navigator().clipboard())
+        .ok_or_else(|| "No browser window".to_string())
+}
+#[cfg(target_family = "wasm")]
+fn set_clipboard_text(text: &str) -> Result<(), String> {
+    let written = wasm_bindgen_futures::JsFuture::from(web_clipboard()?.write_text(text));
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(err) = written.await {
+            warn!("Can't copy moves: {:?}", err);
+        }
+    });
+    Ok(())
+}
+// Text of the clipboard, sent once the browser gives it
+#[cfg(target_family = "wasm")]
+fn get_clipboard_text() -> Receiver<Result<String, String>> {
+    let (sender, receiver) = channel();
+    match web_clipboard() {
+        Ok(clipboard) => {
+            let text = wasm_bindgen_futures::JsFuture::from(clipboard.read_text());
+            wasm_bindgen_futures::spawn_local(async move {
+                let text = text
+                    .await
+                    .map_err(|err| format!("{:?}", err))
+                    .and_then(|text| {
+                        text.as_string()
+                            .ok_or_else(|| "The clipboard doesn't hold text".to_string())
+                    });
+                sender.send(text).ok();
+            });
+        }
+        Err(err) => {
+            sender.send(Err(err)).ok();
+        }
+    }
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn positions_are_counted_from_the_top_left() {
+        use PuzzleAction::*;
+        let size = (3, 4);
+        let actions = [
+            SetActivePosition((2, 0)),
+            SetActivePosition((0, 3)),
+            SetActivePosition((1, 2)),
+        ];
+        let text = "a@1,1 a@3,4 a@2,3";
+        assert_eq!(format_moves(&actions, size), text);
+        assert_eq!(parse_moves(text, size), Ok(actions.to_vec()));
+        assert_eq!(
+            parse_moves(" U  DL\n+ X ", size),
+            Ok(vec![MoveUp, MoveDownLeft, ActiveRotateCW, ActiveFlipX])
+        );
+    }
+
+    #[test]
+    fn malformed_moves_are_rejected() {
+        for text in [
+            "a@0,1",
+            "a@4,1",
+            "a@1,0",
+            "a@1,5",
+            "a@1",
+            "a@1,1,1",
+            "a@x,1",
+            "a@-1,1",
+            "b@1,1",
+            "@1,1",
+            "UU",
+            "u",
+            "U a@9,9 L",
+        ] {
+            assert!(parse_moves(text, (3, 4)).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn formatted_moves_replay_the_same_puzzle() {
+        use PuzzleAction::*;
+        let selections = [
+            MoveActiveLeft,
+            MoveActiveRight,
+            MoveActiveUp,
+            MoveActiveDown,
+        ];
+        let cases: [(&str, &[PuzzleAction]); 3] = [
+            (
+                "4x4:1,2,3,4/5,6W,7,8/9,.,10,12W/13,14,11,.",
+                &[
+                    MoveUp,
+                    MoveDown,
+                    MoveLeft,
+                    MoveRight,
+                    MoveActive,
+                    NextHole,
+                    ActiveFlipX,
+                    ActiveFlipY,
+                    ActiveRotateCW,
+                    ActiveRotateCCW,
+                ],
+            ),
+            // Diagonal slides of hexagonal grids
+            (
+                "3x3+hex:1,2,3/4,.,6/7,5,8",
+                &[
+                    MoveUp,
+                    MoveDown,
+                    MoveLeft,
+                    MoveRight,
+                    MoveUpLeft,
+                    MoveUpRight,
+                    MoveDownLeft,
+                    MoveDownRight,
+                    ActiveFlipX,
+                    ActiveRotateCW,
+                ],
+            ),
+            // Shifts of Loopover puzzles are written as slides
+            (
+                "3x3+loopover:3,1,2R2/4,5,6/7,8FY,9",
+                &[
+                    MoveUp,
+                    MoveDown,
+                    MoveLeft,
+                    MoveRight,
+                    ActiveFlipY,
+                    ActiveRotateCCW,
+                ],
+            ),
+        ];
+        for (state, actions) in cases {
+            let start = Puzzle::from_text(state, Handle::default()).expect("Invalid state");
+            let size = start.size();
+            let mut rng = StdRng::seed_from_u64(3);
+            let mut played = start.clone();
+            for _ in 0..200 {
+                let action = if rng.gen_bool(0.2) {
+                    selections.choose(&mut rng)
+                } else {
+                    actions.choose(&mut rng)
+                };
+                played.apply_action(*action.expect("Oops"));
+            }
+            let text = format_moves(&played.history, size);
+            let parsed = parse_moves(&text, size).expect("Invalid moves");
+            assert_eq!(format_moves(&parsed, size), text);
+            let mut replayed = start.clone();
+            for action in parsed {
+                replayed.apply_action(action);
+            }
+            assert_eq!(replayed.to_text(), played.to_text(), "Moves: {}", text);
+            assert_eq!(replayed.actions_count, played.actions_count);
+        }
+    }
+}
@@ -0,0 +1,592 @@
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+
+// Marks the cells of the search grid that don't hold a movable tile
+const HOLE: u16 = u16::MAX;
+const WALL: u16 = u16::MAX - 1;
+// Distance between cells that no tile can travel
+const UNREACHABLE: u32 = u32::MAX / 1024;
+// Orientation actions tried to fix a tile, in order of preference
+const ORIENTATION_ACTIONS: [PuzzleAction; 4] = [
+    PuzzleAction::ActiveRotateCW,
+    PuzzleAction::ActiveRotateCCW,
+    PuzzleAction::ActiveFlipX,
+    PuzzleAction::ActiveFlipY,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverError {
+    // The search was stopped after expanding the maximum number of nodes
+    TooHard,
+    // Some tile can't reach its position, like behind walls, or the tiles are in an order
+    // that no sequence of moves reaches, like two swapped tiles of a classic puzzle
+    Unsolvable,
+}
+impl std::fmt::Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::TooHard => write!(f, "search stopped at the maximum number of nodes"),
+            SolverError::Unsolvable => write!(f, "some tiles can't reach their position"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    // Actions to apply to the puzzle, in order, for it to be solved
+    pub actions: Vec<PuzzleAction>,
+    // Number of slides or shifts, which is the optimal number for the tile positions
+    pub moves: usize,
+    pub expanded_nodes: usize,
+}
+
+// Solver of the tile positions, with an IDA* search that finds the fewest slides or shifts.
+// Tile orientations are independent of their positions, they are fixed afterwards tile by tile.
+#[derive(Debug, Clone, Copy)]
+pub struct Solver {
+    pub max_nodes: usize,
+}
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            max_nodes: 20_000_000,
+        }
+    }
+}
+
+// Move of the search, converted to puzzle actions once a solution is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchMove {
+    // Index of the hole, and of the move candidate of the puzzle shape
+    Slide(usize, usize),
+    // Index of the row, and true for a shift to the right
+    ShiftRow(usize, bool),
+    // Index of the column, and true for a shift up
+    ShiftColumn(usize, bool),
+}
+
+enum SearchResult {
+    Found,
+    // Smallest estimated cost above the bound, for the next iteration
+    Above(u32),
+    Aborted,
+}
+
+struct Search {
+    kind: PuzzleKind,
+    size: Coord,
+    // Cell index where each tile belongs, by cell index (row * width + column)
+    cells: Vec<u16>,
+    holes: Vec<usize>,
+    // Source cell of each move candidate, by destination cell
+    sources: Vec<Vec<Option<usize>>>,
+    // Distance between every two cells, for a tile moving through the grid
+    distances: Vec<u32>,
+    path: Vec<SearchMove>,
+    expanded_nodes: usize,
+    max_nodes: usize,
+}
+impl Search {
+    fn new(puzzle: &Puzzle, max_nodes: usize) -> Self {
+        let size = puzzle.size();
+        let n_cells = size.0 * size.1;
+        let index = |(row, column): Coord| row * size.1 + column;
+        let cells = puzzle
+            .tiles
+            .iter()
+            .map(|tile| match tile {
+                None => HOLE,
+                Some(tile) if tile.fixed => WALL,
+                Some(tile) => index(tile.position) as u16,
+            })
+            .collect::<Vec<_>>();
+        let sources = (0..n_cells)
+            .map(|cell| {
+                let position = (cell / size.1, cell % size.1);
+                puzzle
+                    .move_candidates()
+                    .iter()
+                    .map(|action| puzzle.move_source(position, *action).map(index))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        let distances = match puzzle.kind {
+            PuzzleKind::Taquin => {
+                // Walls never move, tiles go around them
+                let blocked = cells.iter().map(|cell| *cell == WALL).collect::<Vec<_>>();
+                (0..n_cells)
+                    .flat_map(|from| bfs_distances(from, &sources, &blocked))
+                    .collect()
+            }
+            PuzzleKind::Loopover => (0..n_cells)
+                .flat_map(|from| {
+                    (0..n_cells).map(move |to| {
+                        let wrapped = |a: usize, b: usize, len: usize| {
+                            let delta = a.abs_diff(b);
+                            delta.min(len - delta) as u32
+                        };
+                        wrapped(from / size.1, to / size.1, size.0)
+                            + wrapped(from % size.1, to % size.1, size.1)
+                    })
+                })
+                .collect(),
+        };
+        Self {
+            kind: puzzle.kind,
+            size,
+            holes: puzzle.holes.iter().map(|hole| index(*hole)).collect(),
+            cells,
+            sources,
+            distances,
+            path: vec![],
+            expanded_nodes: 0,
+            max_nodes,
+        }
+    }
+    fn distance(&self, from: usize, to: usize) -> u32 {
+        self.distances[from * self.cells.len() + to]
+    }
+    fn tile_distances(&self) -> u32 {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile != HOLE && **tile != WALL)
+            .map(|(cell, tile)| self.distance(cell, *tile as usize))
+            .sum()
+    }
+    // Lower bound of the number of moves left.
+    // A slide moves one tile by one cell, a shift moves a full row or column by one cell.
+    fn heuristic(&self) -> u32 {
+        match self.kind {
+            PuzzleKind::Taquin => self.tile_distances(),
+            PuzzleKind::Loopover => self
+                .tile_distances()
+                .div_ceil(self.size.0.max(self.size.1) as u32),
+        }
+    }
+    fn run(&mut self) -> Result<(), SolverError> {
+        let mut bound = self.heuristic();
+        if bound >= UNREACHABLE || !self.has_solvable_parity() {
+            return Err(SolverError::Unsolvable);
+        }
+        loop {
+            match self.search(0, bound) {
+                SearchResult::Found => return Ok(()),
+                SearchResult::Above(next_bound) => bound = next_bound,
+                SearchResult::Aborted => return Err(SolverError::TooHard),
+            }
+        }
+    }
+    fn search(&mut self, cost: u32, bound: u32) -> SearchResult {
+        let heuristic = self.heuristic();
+        if cost + heuristic > bound {
+            return SearchResult::Above(cost + heuristic);
+        }
+        if heuristic == 0 {
+            return SearchResult::Found;
+        }
+        self.expanded_nodes += 1;
+        if self.expanded_nodes > self.max_nodes {
+            return SearchResult::Aborted;
+        }
+        let mut next_bound = u32::MAX;
+        for search_move in self.moves() {
+            // The reverse move is found before applying the move, from the hole it starts at
+            let undo = self.reverse(search_move);
+            // Undoing the previous move never leads to a shorter solution
+            if self.path.last() == Some(&undo) {
+                continue;
+            }
+            self.apply(search_move);
+            self.path.push(search_move);
+            match self.search(cost + 1, bound) {
+                SearchResult::Found => return SearchResult::Found,
+                SearchResult::Above(estimate) => next_bound = next_bound.min(estimate),
+                SearchResult::Aborted => return SearchResult::Aborted,
+            }
+            self.path.pop();
+            self.apply(undo);
+        }
+        SearchResult::Above(next_bound)
+    }
+    // Every move is a permutation of the cells, so when all of them have the same parity
+    // the parity of the tile order can't change and half of the orders are unreachable.
+    // A slide swaps the hole with a tile, so on grids whose cells can be colored in two
+    // colors, with neighbours of different colors, the permutation parity follows the color
+    // of the cell holding the only hole. A shift is even when its row or column is odd.
+    fn has_solvable_parity(&self) -> bool {
+        let n_cells = self.cells.len();
+        let mut targeted = vec![false; n_cells];
+        for tile in self
+            .cells
+            .iter()
+            .filter(|tile| **tile != HOLE && **tile != WALL)
+        {
+            targeted[*tile as usize] = true;
+        }
+        let hole_target = (0..n_cells).find(|cell| self.cells[*cell] != WALL && !targeted[*cell]);
+        // Walls never move and stay fixed points of the permutation
+        let targets = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(cell, tile)| match *tile {
+                WALL => cell,
+                HOLE => hole_target.unwrap_or(cell),
+                tile => tile as usize,
+            })
+            .collect::<Vec<_>>();
+        let mut visited = vec![false; n_cells];
+        let mut n_cycles = 0;
+        for start in 0..n_cells {
+            if visited[start] {
+                continue;
+            }
+            n_cycles += 1;
+            let mut cell = start;
+            while !visited[cell] {
+                visited[cell] = true;
+                cell = targets[cell];
+            }
+        }
+        let is_odd = (n_cells - n_cycles) % 2 == 1;
+        match self.kind {
+            // Several holes are interchangeable, any tile order can then be reached
+            PuzzleKind::Taquin if self.holes.len() != 1 => true,
+            PuzzleKind::Taquin => {
+                let (Some(colors), Some(hole_target)) = (self.two_colors(), hole_target) else {
+                    return true;
+                };
+                is_odd == (colors[self.holes[0]] != colors[hole_target])
+            }
+            PuzzleKind::Loopover => {
+                self.size.0.is_multiple_of(2) || self.size.1.is_multiple_of(2) || !is_odd
+            }
+        }
+    }
+    // Colors of the cells, with every two neighbours of different colors, if possible.
+    // Square grids always have such colors, hexagonal grids and odd tori don't.
+    fn two_colors(&self) -> Option<Vec<bool>> {
+        let mut colors = vec![None; self.cells.len()];
+        for start in 0..self.cells.len() {
+            if colors[start].is_some() {
+                continue;
+            }
+            colors[start] = Some(false);
+            let mut queue = VecDeque::from([(start, false)]);
+            while let Some((cell, color)) = queue.pop_front() {
+                for neighbour in self.sources[cell].iter().flatten() {
+                    match colors[*neighbour] {
+                        None => {
+                            colors[*neighbour] = Some(!color);
+                            queue.push_back((*neighbour, !color));
+                        }
+                        Some(neighbour_color) if neighbour_color == color => return None,
+                        Some(_) => (),
+                    }
+                }
+            }
+        }
+        colors.into_iter().collect()
+    }
+    fn moves(&self) -> Vec<SearchMove> {
+        match self.kind {
+            PuzzleKind::Taquin => self
+                .holes
+                .iter()
+                .enumerate()
+                .flat_map(|(hole_index, hole)| {
+                    self.sources[*hole]
+                        .iter()
+                        .enumerate()
+                        .filter(move |(_, source)| {
+                            source.is_some_and(|source| {
+                                self.cells[source] != HOLE && self.cells[source] != WALL
+                            })
+                        })
+                        .map(move |(candidate, _)| SearchMove::Slide(hole_index, candidate))
+                })
+                .collect(),
+            PuzzleKind::Loopover => (0..self.size.0)
+                .flat_map(|row| {
+                    [
+                        SearchMove::ShiftRow(row, true),
+                        SearchMove::ShiftRow(row, false),
+                    ]
+                })
+                .chain((0..self.size.1).flat_map(|column| {
+                    [
+                        SearchMove::ShiftColumn(column, true),
+                        SearchMove::ShiftColumn(column, false),
+                    ]
+                }))
+                .collect(),
+        }
+    }
+    fn reverse(&self, search_move: SearchMove) -> SearchMove {
+        match search_move {
+            // The tile goes back into the cell the hole came from
+            SearchMove::Slide(hole_index, candidate) => {
+                let hole = self.holes[hole_index];
+                let source = self.sources[hole][candidate].expect("Invalid slide");
+                let back = self.sources[source]
+                    .iter()
+                    .position(|back_source| *back_source == Some(hole))
+                    .expect("Slides are reversible");
+                SearchMove::Slide(hole_index, back)
+            }
+            SearchMove::ShiftRow(row, right) => SearchMove::ShiftRow(row, !right),
+            SearchMove::ShiftColumn(column, up) => SearchMove::ShiftColumn(column, !up),
+        }
+    }
+    fn apply(&mut self, search_move: SearchMove) {
+        let (height, width) = self.size;
+        match search_move {
+            SearchMove::Slide(hole_index, candidate) => {
+                let hole = self.holes[hole_index];
+                let source = self.sources[hole][candidate].expect("Invalid slide");
+                self.cells.swap(hole, source);
+                self.holes[hole_index] = source;
+            }
+            SearchMove::ShiftRow(row, right) => {
+                let line = &mut self.cells[row * width..(row + 1) * width];
+                if right {
+                    line.rotate_right(1);
+                } else {
+                    line.rotate_left(1);
+                }
+            }
+            SearchMove::ShiftColumn(column, up) => {
+                let mut line = (0..height)
+                    .map(|row| self.cells[row * width + column])
+                    .collect::<Vec<_>>();
+                // Rows go up, so shifting up moves tiles to higher indexes
+                if up {
+                    line.rotate_right(1);
+                } else {
+                    line.rotate_left(1);
+                }
+                for (row, tile) in line.into_iter().enumerate() {
+                    self.cells[row * width + column] = tile;
+                }
+            }
+        }
+    }
+}
+
+// Distances from a cell to every other cell, going through neighbours that are not blocked
+fn bfs_distances(from: usize, sources: &[Vec<Option<usize>>], blocked: &[bool]) -> Vec<u32> {
+    let mut distances = vec![UNREACHABLE; sources.len()];
+    distances[from] = 0;
+    let mut queue = VecDeque::from([from]);
+    while let Some(cell) = queue.pop_front() {
+        for neighbour in sources[cell].iter().flatten() {
+            if !blocked[*neighbour] && distances[*neighbour] == UNREACHABLE {
+                distances[*neighbour] = distances[cell] + 1;
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    distances
+}
+
+impl Solver {
+    pub fn solve(&self, puzzle: &Puzzle) -> Result<Solution, SolverError> {
+        let mut search = Search::new(puzzle, self.max_nodes);
+        search.run()?;
+        let moves = search.path.len();
+        let expanded_nodes = search.expanded_nodes;
+        // Actions are checked on a copy of the puzzle as they are generated, to follow
+        // the active tile and hole the same way the game does
+        let mut solved = puzzle.clone();
+        let mut actions = vec![];
+        let mut push = |solved: &mut Puzzle, action: PuzzleAction| {
+            solved.apply_action(action);
+            actions.push(action);
+        };
+        for search_move in search.path {
+            match search_move {
+                SearchMove::Slide(hole_index, candidate) => {
+                    if solved.active_hole != hole_index {
+                        let hole = solved.holes[hole_index];
+                        push(&mut solved, PuzzleAction::SetActivePosition(hole));
+                    }
+                    let action = solved.move_candidates()[candidate];
+                    push(&mut solved, action);
+                }
+                SearchMove::ShiftRow(row, right) => {
+                    if solved.active.0 != row {
+                        let position = (row, solved.active.1);
+                        push(&mut solved, PuzzleAction::SetActivePosition(position));
+                    }
+                    push(
+                        &mut solved,
+                        if right {
+                            PuzzleAction::ShiftRowRight
+                        } else {
+                            PuzzleAction::ShiftRowLeft
+                        },
+                    );
+                }
+                SearchMove::ShiftColumn(column, up) => {
+                    if solved.active.1 != column {
+                        let position = (solved.active.0, column);
+                        push(&mut solved, PuzzleAction::SetActivePosition(position));
+                    }
+                    push(
+                        &mut solved,
+                        if up {
+                            PuzzleAction::ShiftColumnUp
+                        } else {
+                            PuzzleAction::ShiftColumnDown
+                        },
+                    );
+                }
+            }
+        }
+        // Fix the front orientations, then for expert puzzles the back orientations
+        for back in [false, true] {
+            if (back && !solved.expert) || (!back && solved.front_completed) {
+                continue;
+            }
+            let cells = solved
+                .tiles
+                .indexed_iter()
+                .map(|(position, _)| position)
+                .collect::<Vec<_>>();
+            for position in cells {
+                let Some(tile) = solved.tiles[position].as_ref().filter(|tile| !tile.fixed) else {
+                    continue;
+                };
+                let fixes = orientation_fix(tile, back);
+                if !fixes.is_empty() {
+                    if solved.active != position {
+                        push(&mut solved, PuzzleAction::SetActivePosition(position));
+                    }
+                    for action in fixes {
+                        push(&mut solved, action);
+                    }
+                }
+            }
+        }
+        debug_assert!(solved.is_solved, "Solution does not solve the puzzle");
+        Ok(Solution {
+            actions,
+            moves,
+            expanded_nodes,
+        })
+    }
+}
+
+// Fewest orientation actions that make the tile upright, on its front or its back
+fn orientation_fix(tile: &Tile, back: bool) -> Vec<PuzzleAction> {
+    let is_fixed = |tile: &Tile| {
+        if back {
+            tile.is_back_correctly_oriented()
+        } else {
+            tile.is_correctly_oriented()
+        }
+    };
+    let mut queue = VecDeque::from([(tile.clone(), vec![])]);
+    while let Some((tile, actions)) = queue.pop_front() {
+        if is_fixed(&tile) {
+            return actions;
+        }
+        // Any orientation is reached within a rotation and a flip, a half turn at most
+        if actions.len() > tile.rotation.order() as usize {
+            continue;
+        }
+        for action in ORIENTATION_ACTIONS {
+            let mut next = tile.clone();
+            next.apply_orientation_action(action);
+            let mut next_actions = actions.clone();
+            next_actions.push(action);
+            queue.push_back((next, next_actions));
+        }
+    }
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_puzzle(variant: PuzzleVariant, size: Coord, seed: u64) -> Puzzle {
+        let mut options = NewPuzzleOptions::new(variant, &NewPuzzleSettings::default());
+        options.size = size;
+        options.seed = Some(seed);
+        options.scramble = Some(ScrambleActions {
+            slide: true,
+            rotate: true,
+            flip: true,
+        });
+        options.create_puzzle(Handle::default())
+    }
+
+    // Solves the puzzle, and checks that its actions solve it when replayed like the game does
+    fn assert_solves(puzzle: &Puzzle) {
+        let solution = Solver::default()
+            .solve(puzzle)
+            .unwrap_or_else(|error| panic!("No solution for {}: {}", puzzle.to_text(), error));
+        let mut replayed = puzzle.clone();
+        for action in &solution.actions {
+            replayed.apply_action(*action);
+        }
+        assert!(
+            replayed.is_solved,
+            "Solution of {} ends at {}",
+            puzzle.to_text(),
+            replayed.to_text()
+        );
+    }
+
+    #[test]
+    fn solves_seeded_puzzles() {
+        for seed in 0..5 {
+            assert_solves(&new_puzzle(PuzzleVariant::Classic, (3, 3), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Classic, (3, 4), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Torus, (3, 3), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Torus, (4, 4), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Hex, (3, 3), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Hex, (4, 4), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Loopover, (3, 3), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::DoubleSided, (3, 3), seed));
+            assert_solves(&new_puzzle(PuzzleVariant::Expert, (3, 3), seed));
+        }
+    }
+
+    #[test]
+    fn solves_states_with_walls_and_holes() {
+        for state in [
+            "3x3:.,2,3/1,5,6/4,7,8",
+            "3x3+torus:.,2,3/4,5,6/1,8,7",
+            "3x3+hex:2,5,3/1,.,6/4,7,8",
+            "3x3+loopover:3,1,2/4,5,6/7,8,9",
+            "4x4:1,2,3,4/5,6W,7,8/9,.,10,12/13,14,11,.",
+        ] {
+            let puzzle = Puzzle::from_text(state, Handle::default()).expect("Invalid state");
+            assert_solves(&puzzle);
+        }
+    }
+
+    #[test]
+    fn rejects_unsolvable_states() {
+        for state in [
+            // Two swapped tiles
+            "3x3:2,1,3/4,5,6/7,8,.",
+            "4x4+torus:2,1,3,4/5,6,7,8/9,10,11,12/13,14,15,.",
+            "3x3+loopover:2,1,3/4,5,6/7,8,9",
+            // A tile behind walls
+            "3x3:3,2W,1/4W,5,6/7,8,.",
+        ] {
+            let puzzle = Puzzle::from_text(state, Handle::default()).expect("Invalid state");
+            assert_eq!(
+                Solver::default().solve(&puzzle).map(|_| ()),
+                Err(SolverError::Unsolvable),
+                "{}",
+                state
+            );
+        }
+    }
+}
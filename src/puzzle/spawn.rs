@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::prelude::*;
 
-use super::tile_translation_from_position;
+use super::tile_cell_scale;
 
 pub fn spawn_puzzle_entities(
     mut commands: Commands,
@@ -10,6 +10,8 @@ pub fn spawn_puzzle_entities(
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
 ) {
     for (puzzle_entity, mut puzzle) in puzzle.iter_mut() {
         let tile_material = materials.add(StandardMaterial {
@@ -17,29 +19,56 @@ pub fn spawn_puzzle_entities(
             unlit: true,
             ..default()
         });
+        // Walls are darkened to show they can't be moved
+        let wall_material = materials.add(StandardMaterial {
+            base_color: Color::srgb(0.4, 0.4, 0.4),
+            base_color_texture: Some(puzzle.image.clone()),
+            unlit: true,
+            ..default()
+        });
         let size = puzzle.size();
-        let tile_scale = {
-            let scale = TILE_OCCUPANCY / (size.0.max(size.1) as f32);
-            Vec3::new(scale, scale, 1.)
-        };
+        let shape = puzzle.shape;
+        let tile_scale = tile_scale(size, shape, settings.tile_occupancy());
         let solved_tile_scale = {
-            let scale = 1. / (size.0.max(size.1) as f32);
+            let scale = tile_cell_scale(size, shape);
             Vec3::new(scale, scale, 5.)
         };
         let tile_transform = Transform::from_scale(tile_scale);
+        // Outlines get thinner on large grids, where tiles are tiny
+        let outline_width_scale = (8. / size.0.max(size.1) as f32).clamp(0.4, 1.0);
+        let tile_meshes = TileMeshPool::new(
+            size,
+            shape,
+            puzzle.double_sided,
+            puzzle.jigsaw_edges.as_ref(),
+            &mut meshes,
+        );
+        let error_badge_assets = ErrorBadgeAssets::new(&mut meshes, &mut materials, &asset_server);
         let mut solution_tiles = vec![];
         puzzle.tiles.indexed_iter_mut().for_each(|(index, tile)| {
             if let Some(tile) = tile.as_mut() {
-                let mesh = meshes.add(tile.compute_mesh());
+                // Hexagonal tiles are squashed along a screen axis when flipped,
+                // which is only possible by changing their own mesh. MeshFlippingLens edits
+                // it in place, so they can't share the meshes of the pool.
+                let mesh = match shape {
+                    PuzzleShape::Square => tile_meshes.tile_mesh(tile),
+                    PuzzleShape::Hex => meshes.add(tile.compute_mesh()),
+                };
                 tile.entity = Some(
                     commands
                         .spawn((
                             PbrBundle {
-                                material: tile_material.clone(),
+                                material: if tile.fixed {
+                                    wall_material.clone()
+                                } else {
+                                    tile_material.clone()
+                                },
                                 mesh,
                                 transform: tile_transform
                                     .with_rotation(tile.compute_rotation())
-                                    .with_translation(tile_translation_from_position(index, size)),
+                                    .with_translation(tile_translation_from_position(
+                                        index, size, shape,
+                                    )),
                                 ..default()
                             },
                             TileAnimationBundle::default(),
@@ -47,27 +76,26 @@ pub fn spawn_puzzle_entities(
                             OutlineBundle {
                                 outline: OutlineVolume {
                                     visible: false,
-                                    width: 2.0,
-                                    colour: Color::WHITE,
+                                    width: 2.0 * outline_width_scale,
+                                    colour: theme.outline_active,
                                 },
                                 ..default()
                             },
                             On::<Pointer<Move>>::run(tile_on_moving_over),
                             On::<Pointer<Click>>::run(tile_on_click),
                         ))
+                        .with_children(|parent| error_badge_assets.spawn(parent, shape))
                         .id(),
                 );
                 // Duplicate the tile to add to the PuzzleSolution at the real tile position
-                // Need a duplicate mesh, because it must not be flipped when the main tile is flipped
-                let solution_mesh =
-                    meshes.add(compute_tile_mesh(size, tile.position, false, false));
+                let solution_mesh = tile_meshes.get(tile.position, false, false);
                 solution_tiles.push(
                     commands
                         .spawn(PbrBundle {
                             material: tile_material.clone(),
                             mesh: solution_mesh,
                             transform: tile_transform.with_translation(
-                                tile_translation_from_position(tile.position, size),
+                                tile_translation_from_position(tile.position, size, shape),
                             ),
                             ..default()
                         })
@@ -75,35 +103,39 @@ pub fn spawn_puzzle_entities(
                 );
             }
         });
-        // Spawn the hole entity
+        // Spawn the hole entity, if this kind of puzzle has one
         let hole_material = materials.add(Color::srgba(1.0, 1.0, 1.0, 0.0));
-        let hole_mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
-        let hole_entity = commands
-            .spawn(PbrBundle {
-                material: hole_material,
-                mesh: hole_mesh,
-                transform: tile_transform
-                    .with_translation(tile_translation_from_position(puzzle.hole, size)),
-                ..default()
-            })
-            .insert(Name::new(format!(
-                "Hole_Ref_{}x{}",
-                puzzle.hole.1, puzzle.hole.0
-            )))
-            .insert(TileAnimationBundle::default())
-            .insert(OutlineBundle {
-                outline: OutlineVolume {
-                    visible: true,
-                    width: 2.0,
-                    colour: Color::WHITE,
-                },
-                ..default()
+        let hole_entities = puzzle
+            .holes
+            .iter()
+            .map(|hole| {
+                commands
+                    .spawn(PbrBundle {
+                        material: hole_material.clone(),
+                        // Jigsaw holes have the edges of their position
+                        mesh: tile_meshes.get(*hole, false, false),
+                        transform: tile_transform
+                            .with_translation(tile_translation_from_position(*hole, size, shape)),
+                        ..default()
+                    })
+                    .insert(Name::new(format!("Hole_Ref_{}x{}", hole.1, hole.0)))
+                    .insert(TileAnimationBundle::default())
+                    .insert(On::<Pointer<Click>>::run(hole_on_click))
+                    .insert(OutlineBundle {
+                        outline: OutlineVolume {
+                            visible: true,
+                            width: 2.0 * outline_width_scale,
+                            colour: theme.outline_active,
+                        },
+                        ..default()
+                    })
+                    .id()
             })
-            .id();
-        puzzle.hole_entity = Some(hole_entity);
+            .collect::<Vec<_>>();
+        puzzle.hole_entities = hole_entities.clone();
         // Action tip entity
         let action_tip_material = materials.add(StandardMaterial {
-            base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_GRID_ALPHA),
+            base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_grid_alpha()),
             base_color_texture: Some(asset_server.load("ActionTipFullGrid.png")),
             alpha_mode: AlphaMode::Blend,
             unlit: true,
@@ -132,12 +164,12 @@ pub fn spawn_puzzle_entities(
                         is_hoverable: false,
                     },
                     On::<Pointer<Out>>::run(
-                        |_event: Listener<Pointer<Out>>,
+                        |event: Listener<Pointer<Out>>,
                          mut action_tip: Query<
                             &mut Visibility,
                             With<ActionTip>,
                         >| {
-                            if let Ok(mut action_tip) = action_tip.get_single_mut() {
+                            if let Ok(mut action_tip) = action_tip.get_mut(event.listener()) {
                                 *action_tip = Visibility::Hidden;
                             }
                         },
@@ -177,9 +209,9 @@ pub fn spawn_puzzle_entities(
                     .tiles
                     .iter()
                     .filter_map(|tile| tile.as_ref().and_then(|tile| tile.entity))
+                    .chain(hole_entities)
                     .collect::<Vec<_>>(),
             )
-            .add_child(hole_entity)
             .insert(Name::new("Tiles"))
             .insert(PuzzleTiles)
             .id();
@@ -189,7 +221,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::ActiveFlipX,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconFlipX.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -199,7 +231,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::ActiveFlipY,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconFlipY.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -209,7 +241,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::ActiveRotateCCW,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconRotateCCW.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -219,7 +251,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::ActiveRotateCW,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconRotateCW.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -229,7 +261,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::MoveUp,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconMoveUp.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -239,7 +271,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::MoveDown,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconMoveDown.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -249,7 +281,7 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::MoveRight,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconMoveRight.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
@@ -259,13 +291,23 @@ pub fn spawn_puzzle_entities(
         action_tip_materials.insert(
             PuzzleAction::MoveLeft,
             materials.add(StandardMaterial {
-                base_color: Color::srgba(1.0, 1.0, 1.0, ACTION_TIP_ICON_ALPHA),
+                base_color: Color::srgba(1.0, 1.0, 1.0, settings.action_tip_icon_alpha()),
                 base_color_texture: Some(asset_server.load("ActionTipIconMoveLeft.png")),
                 alpha_mode: AlphaMode::Blend,
                 unlit: true,
                 ..default()
             }),
         );
+        // Diagonal moves of hexagonal puzzles reuse the vertical icons
+        for (diagonal, vertical) in [
+            (PuzzleAction::MoveUpLeft, PuzzleAction::MoveUp),
+            (PuzzleAction::MoveUpRight, PuzzleAction::MoveUp),
+            (PuzzleAction::MoveDownLeft, PuzzleAction::MoveDown),
+            (PuzzleAction::MoveDownRight, PuzzleAction::MoveDown),
+        ] {
+            let material = action_tip_materials[&vertical].clone();
+            action_tip_materials.insert(diagonal, material);
+        }
         action_tip_materials.insert(
             PuzzleAction::NoAction,
             materials.add(Color::srgba(1.0, 1.0, 1.0, 0.0)),
@@ -279,12 +321,9 @@ pub fn spawn_puzzle_entities(
             .insert(PuzzleAssets {
                 tile_scale,
                 solved_tile_scale,
-                outline_color_active: Color::WHITE,
-                outline_color_misplaced_misoriented: Color::Srgba(
-                    bevy::color::palettes::css::PURPLE,
-                ),
-                outline_color_misplaced: Color::Srgba(bevy::color::palettes::css::RED),
-                outline_color_misoriented: Color::Srgba(bevy::color::palettes::css::ORANGE),
+                tile_material,
+                tile_meshes,
+                outline_width_scale,
                 action_tip_materials,
             })
             .add_child(puzzle_solution)
@@ -292,3 +331,63 @@ pub fn spawn_puzzle_entities(
             .add_child(puzzle_action_tip);
     }
 }
+
+fn tile_scale(size: Coord, shape: PuzzleShape, occupancy: f32) -> Vec3 {
+    let scale = occupancy * tile_cell_scale(size, shape);
+    Vec3::new(scale, scale, 1.)
+}
+
+// Applies the tile gap and action tip opacity of changed settings to the current puzzles
+pub fn apply_puzzle_settings(
+    settings: Res<Settings>,
+    mut puzzle: Query<(&Puzzle, &mut PuzzleAssets)>,
+    mut transforms: Query<&mut Transform>,
+    children: Query<&Children>,
+    action_tip: Query<&Handle<StandardMaterial>, With<ActionTip>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (puzzle, mut puzzle_assets) in puzzle.iter_mut() {
+        puzzle_assets.tile_scale =
+            tile_scale(puzzle.size(), puzzle.shape, settings.tile_occupancy());
+        let action_tip = puzzle
+            .action_tip_entity
+            .into_iter()
+            .flat_map(|entity| children.iter_descendants(entity))
+            .find_map(|entity| Some((entity, action_tip.get(entity).ok()?)));
+        // Tiles of a solved puzzle are joined, they get the new scale when a new puzzle is created
+        if !puzzle.is_solved {
+            let entities = puzzle
+                .tiles
+                .iter()
+                .filter_map(|tile| tile.as_ref().and_then(|tile| tile.entity))
+                .chain(puzzle.hole_entities.iter().copied())
+                .chain(action_tip.map(|(entity, _)| entity));
+            for entity in entities {
+                if let Ok(mut transform) = transforms.get_mut(entity) {
+                    transform.scale = puzzle_assets.tile_scale;
+                }
+            }
+        }
+        if let Some((_, material)) = action_tip {
+            if let Some(material) = materials.get_mut(material) {
+                material
+                    .base_color
+                    .set_alpha(settings.action_tip_grid_alpha());
+            }
+        }
+        for (action, material) in puzzle_assets.action_tip_materials.iter() {
+            // No icon is shown for tiles without action
+            if action == &PuzzleAction::NoAction {
+                continue;
+            }
+            if let Some(material) = materials.get_mut(material) {
+                material
+                    .base_color
+                    .set_alpha(settings.action_tip_icon_alpha());
+            }
+        }
+    }
+}
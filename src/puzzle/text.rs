@@ -0,0 +1,333 @@
+use grid::Grid;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::prelude::*;
+
+// Text format of a puzzle state, to share a puzzle without its seed or to report a bug:
+//   <WIDTH>x<HEIGHT>[+<FLAG>...]:<ROW>/<ROW>/...
+// Flags are hex, torus, loopover, double, expert and back, for an expert puzzle
+// whose front has been completed.
+// Rows go from the top of the grid to the bottom, with comma separated cells.
+// A cell is . for a hole, otherwise the number of its tile in reading order of the
+// solved puzzle, followed by W for a wall, R<STEPS> for a clock-wise rotation,
+// R alone being one step, and FX, FY or FXY for flips on the local tile axes.
+// For example 4x4:15R,3,.,7FX/...
+const FLAGS: [&str; 6] = ["hex", "torus", "loopover", "double", "expert", "back"];
+
+impl Puzzle {
+    pub fn to_text(&self) -> String {
+        let (height, width) = self.size();
+        let flags = [
+            self.shape == PuzzleShape::Hex,
+            self.wrapping,
+            self.kind == PuzzleKind::Loopover,
+            self.double_sided,
+            self.expert,
+            self.targets_back(),
+        ];
+        let header = FLAGS
+            .into_iter()
+            .zip(flags)
+            .filter(|(_, flag)| *flag)
+            .fold(format!("{}x{}", width, height), |header, (name, _)| {
+                header + "+" + name
+            });
+        let rows = (0..height)
+            .rev()
+            .map(|row| {
+                (0..width)
+                    .map(|column| match &self.tiles[(row, column)] {
+                        None => ".".to_string(),
+                        Some(tile) => format_tile(tile, self.size()),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>();
+        format!("{}:{}", header, rows.join("/"))
+    }
+    // Puzzle of a state written with to_text, showing the given image.
+    // The first hole is the active one.
+    pub fn from_text(text: &str, image: Handle<Image>) -> Result<Self, String> {
+        let (header, rows) = text
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| "Missing : after the puzzle size".to_string())?;
+        let mut header = header.split('+');
+        let (width, height) = header
+            .next()
+            .and_then(|size| size.split_once('x'))
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .ok_or_else(|| "Invalid puzzle size, expected <WIDTH>x<HEIGHT>".to_string())?;
+        // Sizes are the ones of new puzzles, the solver keeps cell indices on 16 bits
+        let range = MIN_PUZZLE_SIZE..=MAX_PUZZLE_SIZE;
+        if !range.contains(&width) || !range.contains(&height) {
+            return Err(format!(
+                "Invalid puzzle size {}x{}, sizes go from {}x{} to {}x{}",
+                width, height, MIN_PUZZLE_SIZE, MIN_PUZZLE_SIZE, MAX_PUZZLE_SIZE, MAX_PUZZLE_SIZE
+            ));
+        }
+        let mut flags = [false; FLAGS.len()];
+        for flag in header {
+            let i = FLAGS
+                .iter()
+                .position(|name| *name == flag)
+                .ok_or_else(|| format!("Unknown puzzle flag {}", flag))?;
+            flags[i] = true;
+        }
+        let [hex, torus, loopover, double_sided, expert, back] = flags;
+        if hex && (torus || loopover) {
+            return Err("Hexagonal puzzles only support Taquin without wrapping".to_string());
+        }
+        if (expert && !double_sided) || (back && !expert) {
+            return Err("Expert puzzles must be double-sided, back requires expert".to_string());
+        }
+        let config = PuzzleConfig {
+            width,
+            height,
+            kind: if loopover {
+                PuzzleKind::Loopover
+            } else {
+                PuzzleKind::Taquin
+            },
+            shape: if hex {
+                PuzzleShape::Hex
+            } else {
+                PuzzleShape::Square
+            },
+            wrapping: torus,
+            double_sided,
+            expert,
+            ..default()
+        };
+        let size = config.size();
+        let rows = rows.split('/').collect::<Vec<_>>();
+        if rows.len() != height {
+            return Err(format!("Expected {} rows, found {}", height, rows.len()));
+        }
+        let mut tiles = Grid::new(height, width);
+        let mut holes = vec![];
+        let mut used = vec![false; width * height];
+        for (row, cells) in (0..height).rev().zip(rows) {
+            let cells = cells.split(',').collect::<Vec<_>>();
+            if cells.len() != width {
+                return Err(format!(
+                    "Expected {} cells in row {}, found {}",
+                    width,
+                    height - row,
+                    cells.len()
+                ));
+            }
+            for (column, cell) in cells.into_iter().enumerate() {
+                let cell = cell.trim();
+                if cell == "." {
+                    holes.push((row, column));
+                    continue;
+                }
+                let mut tile = parse_tile(cell, size, config.shape)
+                    .ok_or_else(|| format!("Invalid cell {}", cell))?;
+                let index = tile.position.0 * width + tile.position.1;
+                if std::mem::replace(&mut used[index], true) {
+                    return Err(format!("Tile {} is used twice", cell));
+                }
+                if tile.fixed && tile.position != (row, column) {
+                    return Err(format!("Wall {} is not at its position", cell));
+                }
+                tile.double_sided = double_sided;
+                tiles[(row, column)] = Some(tile);
+            }
+        }
+        // Holes are found in reading order, bottom right first is the order of new puzzles
+        holes.reverse();
+        match config.kind {
+            PuzzleKind::Taquin if holes.is_empty() => {
+                return Err("Taquin puzzles need a hole".to_string())
+            }
+            PuzzleKind::Taquin if holes.len() == width * height => {
+                return Err("Taquin puzzles need a tile".to_string())
+            }
+            PuzzleKind::Loopover if !holes.is_empty() => {
+                return Err("Loopover puzzles have no hole".to_string())
+            }
+            _ => (),
+        }
+        let mut puzzle = Puzzle::new(
+            image,
+            &PuzzleConfig {
+                n_holes: holes.len().max(1),
+                ..config
+            },
+            StdRng::seed_from_u64(0),
+        );
+        puzzle.tiles = tiles;
+        puzzle.active = holes.first().copied().unwrap_or((0, 0));
+        puzzle.holes = holes;
+        puzzle.front_completed = back;
+        puzzle.compute_solved();
+        Ok(puzzle)
+    }
+}
+
+fn format_tile(tile: &Tile, size: Coord) -> String {
+    let (height, width) = size;
+    let mut text = ((height - 1 - tile.position.0) * width + tile.position.1 + 1).to_string();
+    if tile.fixed {
+        text.push('W');
+    }
+    match tile.rotation.steps() {
+        0 => (),
+        1 => text.push('R'),
+        steps => text.push_str(&format!("R{}", steps)),
+    }
+    match tile.flips() {
+        (false, false) => (),
+        (true, false) => text.push_str("FX"),
+        (false, true) => text.push_str("FY"),
+        (true, true) => text.push_str("FXY"),
+    }
+    text
+}
+
+fn parse_tile(text: &str, size: Coord, shape: PuzzleShape) -> Option<Tile> {
+    let (height, width) = size;
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number = text[..digits].parse::<usize>().ok()?;
+    if !(1..=width * height).contains(&number) {
+        return None;
+    }
+    let position = (height - 1 - (number - 1) / width, (number - 1) % width);
+    let mut tile = Tile::new(position, size, shape);
+    let mut suffix = &text[digits..];
+    if let Some(rest) = suffix.strip_prefix('W') {
+        tile.fixed = true;
+        suffix = rest;
+    }
+    if let Some(rest) = suffix.strip_prefix('R') {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let steps = match &rest[..digits] {
+            "" => 1,
+            steps => steps.parse().ok()?,
+        };
+        if steps >= shape.rotation_order() {
+            return None;
+        }
+        tile.rotation = CwRotation::from_steps(steps, shape.rotation_order());
+        suffix = &rest[digits..];
+    }
+    let flips = match suffix {
+        "" => (false, false),
+        "FX" => (true, false),
+        "FY" => (false, true),
+        "FXY" => (true, true),
+        _ => return None,
+    };
+    tile.set_flips(flips.0, flips.1);
+    // Walls are never flipped nor rotated
+    (!tile.fixed || (tile.rotation.is_identity() && flips == (false, false))).then_some(tile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip_is_stable() {
+        for state in [
+            "3x3:.,2,3/1,5,6/4,7,8",
+            "3x3:2R,1FX,3/4R3,5FXY,6FY/7,8,.",
+            "3x2+hex:2R5,.,1/4FX,3,6R2",
+            "3x3+torus:.,2,3/4,5,6R/1,8,7FY",
+            "3x3+loopover:3,1,2R2/4,5,6/7,8FY,9",
+            "4x4:1,2,3,4/5,6W,7,8/9,.,10,12W/13,14,11,.",
+            "3x3:.,2,./4,.,6/7,8R,1",
+            "2x2+double:1FX,2/.,3",
+            "2x2+double+expert:1FX,2/.,3R",
+            "2x2+double+expert+back:1FX,2/.,3R",
+        ] {
+            let puzzle = Puzzle::from_text(state, Handle::default()).expect("Invalid state");
+            let text = puzzle.to_text();
+            assert_eq!(text, state);
+            let reparsed = Puzzle::from_text(&text, Handle::default()).expect("Invalid state");
+            assert_eq!(reparsed.to_text(), text);
+            assert_eq!(reparsed.holes, puzzle.holes);
+            assert_eq!(reparsed.targets_back(), puzzle.targets_back());
+        }
+    }
+
+    #[test]
+    fn text_of_new_puzzles_round_trips() {
+        let configs = [
+            PuzzleConfig::default(),
+            PuzzleConfig {
+                width: 4,
+                height: 3,
+                wrapping: true,
+                n_holes: 2,
+                n_walls: 2,
+                random_hole: true,
+                ..default()
+            },
+            PuzzleConfig {
+                shape: PuzzleShape::Hex,
+                ..default()
+            },
+            PuzzleConfig {
+                kind: PuzzleKind::Loopover,
+                ..default()
+            },
+            PuzzleConfig {
+                width: 6,
+                height: 6,
+                double_sided: true,
+                expert: true,
+                ..default()
+            },
+        ];
+        for config in configs {
+            let mut rng = StdRng::seed_from_u64(7);
+            let mut puzzle = Puzzle::new(Handle::default(), &config, &mut rng);
+            puzzle.shuffle(50, 0.5, 0.5, &mut rng);
+            let text = puzzle.to_text();
+            let reparsed = Puzzle::from_text(&text, Handle::default()).expect("Invalid state");
+            assert_eq!(reparsed.to_text(), text);
+        }
+    }
+
+    #[test]
+    fn malformed_text_is_rejected() {
+        for text in [
+            "",
+            "3x3",
+            "3:1,2,3/4,5,6/7,8,.",
+            "0x3:",
+            "1x3:1/2/.",
+            "3x21:",
+            "3x3+spiral:1,2,3/4,5,6/7,8,.",
+            "3x3+hex+torus:1,2,3/4,5,6/7,8,.",
+            "3x3+expert:1,2,3/4,5,6/7,8,.",
+            "3x3+double+back:1,2,3/4,5,6/7,8,.",
+            "3x3:1,2,3/4,5,6",
+            "3x3:1,2/4,5,6/7,8,.",
+            "3x3:1,1,3/4,5,6/7,8,.",
+            "3x3:10,2,3/4,5,6/7,8,.",
+            "3x3:1Q,2,3/4,5,6/7,8,.",
+            "3x3:1R4,2,3/4,5,6/7,8,.",
+            "3x3+hex:1R6,2,3/4,5,6/7,8,.",
+            "3x3:2W,1,3/4,5,6/7,8,.",
+            "3x3:1WR,2,3/4,5,6/7,8,.",
+            "3x3:1,2,3/4,5,6/7,8,9",
+            "3x3:.,.,./.,.,./.,.,.",
+            "3x3+loopover:1,2,3/4,5,6/7,8,.",
+        ] {
+            assert!(
+                Puzzle::from_text(text, Handle::default()).is_err(),
+                "{} was accepted",
+                text
+            );
+        }
+    }
+}
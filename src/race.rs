@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+// Online race protocol, between the game and the taquin-relay server.
+// Each line is a JSON object with a "type" field, players send:
+//   {"type":"join"}, to take part in the next race
+//   {"type":"progress","progress":0.5,"actions_count":12}, after their actions
+//   {"type":"solved","actions_count":40}, once their puzzle is solved
+// and the relay sends:
+//   {"type":"welcome","player":1}, the number of the player once connected
+//   {"type":"start","players":[1,2],"config":{"size":"4x4","seed":7}}, the puzzle of a
+//   race with the optional mode and actions of the command line options
+//   {"type":"progress","player":2,"progress":0.5,"actions_count":12}
+//   {"type":"winner","player":2,"actions_count":40}, which ends the race, also sent
+//   to the last player of a race the others left
+//   {"type":"left","player":2}
+// Progress is the fraction of tiles at their place, as given by Puzzle::progress.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceRequest {
+    Join,
+    Progress { progress: f32, actions_count: usize },
+    Solved { actions_count: usize },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceMessage {
+    Welcome {
+        player: usize,
+    },
+    Start {
+        players: Vec<usize>,
+        config: RaceConfig,
+    },
+    Progress {
+        player: usize,
+        progress: f32,
+        actions_count: usize,
+    },
+    Winner {
+        player: usize,
+        actions_count: usize,
+    },
+    Left {
+        player: usize,
+    },
+    Error {
+        message: String,
+    },
+}
+
+// Puzzle of a race, written as command line options.
+// Every option that changes the puzzle is here, so that the settings of the players
+// don't make their puzzles differ.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RaceConfig {
+    pub size: String,
+    pub seed: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hole: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub jigsaw: bool,
+}
+impl RaceConfig {
+    // Options of the puzzle, the same scramble for every player
+    pub fn new_puzzle(&self) -> Result<NewPuzzleOptions, String> {
+        let args = [
+            ("--size", Some(self.size.clone())),
+            ("--seed", Some(self.seed.to_string())),
+            ("--mode", self.mode.clone()),
+            ("--actions", self.actions.clone()),
+            ("--hole", self.hole.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{}={}", name, value?)));
+        let cli_options = CliOptions::parse(args)?;
+        let new_puzzle = NewPuzzleSettings {
+            jigsaw: self.jigsaw,
+            ..default()
+        };
+        Ok(cli_options.new_puzzle(&new_puzzle).expect("Oops"))
+    }
+}
+
+// Progress of a player in the current race
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RaceProgress {
+    pub progress: f32,
+    pub actions_count: usize,
+}
+
+// Connection to the relay, given with --race
+#[derive(Resource)]
+pub struct RaceClient {
+    messages: Mutex<Receiver<Result<RaceMessage, String>>>,
+    requests: Sender<String>,
+    // Number given by the relay
+    pub player: Option<usize>,
+    // Puzzle of the current race
+    pub puzzle: Option<Entity>,
+    // Other players of the current race
+    pub opponents: BTreeMap<usize, RaceProgress>,
+    pub winner: Option<usize>,
+    // Joined, until the next race starts
+    pub waiting: bool,
+}
+impl RaceClient {
+    pub fn connect(relay: &str) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(relay)?;
+        let mut writer = stream.try_clone()?;
+        let (requests, requests_receiver) = channel::<String>();
+        let (messages, receiver) = channel();
+        std::thread::spawn(move || {
+            for request in requests_receiver {
+                if writeln!(writer, "{}", request).is_err() {
+                    break;
+                }
+            }
+        });
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let message = serde_json::from_str(&line).map_err(|err| err.to_string());
+                if messages.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            messages: Mutex::new(receiver),
+            requests,
+            player: None,
+            puzzle: None,
+            opponents: BTreeMap::new(),
+            winner: None,
+            waiting: false,
+        })
+    }
+    // A lost relay is noticed when reading its messages
+    fn send(&self, request: &RaceRequest) {
+        let _ = self
+            .requests
+            .send(serde_json::to_string(request).expect("Oops"));
+    }
+    pub fn join(&mut self) {
+        self.waiting = true;
+        self.send(&RaceRequest::Join);
+    }
+}
+
+// Header part with the progress bars of the opponents
+#[derive(Component)]
+pub struct RaceProgressBars;
+
+// Joins the races of the relay given on the command line
+pub fn start_race_client(
+    mut commands: Commands,
+    cli_options: Res<CliOptions>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let Some(relay) = &cli_options.race else {
+        return;
+    };
+    match RaceClient::connect(relay) {
+        Ok(mut client) => {
+            client.join();
+            commands.insert_resource(client);
+        }
+        Err(err) => {
+            error!("Can't connect to the race relay {}: {}", relay, err);
+            app_exit_events.send(AppExit::error());
+        }
+    }
+}
+
+pub fn handle_race_messages(
+    mut commands: Commands,
+    mut client: ResMut<RaceClient>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    puzzles: Query<Entity, With<Puzzle>>,
+) {
+    let (messages, disconnected) = {
+        let receiver = client.messages.lock().expect("Oops");
+        let messages = receiver.try_iter().collect::<Vec<_>>();
+        let disconnected = matches!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        (messages, disconnected)
+    };
+    for message in messages {
+        match message {
+            Err(err) => warn!("Invalid race message: {}", err),
+            Ok(RaceMessage::Welcome { player }) => client.player = Some(player),
+            Ok(RaceMessage::Start { players, config }) => match config.new_puzzle() {
+                Ok(options) => {
+                    for entity in puzzles.iter() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                    let puzzle =
+                        spawn_new_puzzle(&mut commands, &mut images, &asset_server, &options);
+                    let player = client.player;
+                    client.puzzle = Some(puzzle);
+                    client.opponents = players
+                        .into_iter()
+                        .filter(|opponent| Some(*opponent) != player)
+                        .map(|opponent| (opponent, RaceProgress::default()))
+                        .collect();
+                    client.winner = None;
+                    client.waiting = false;
+                    next_state.set(GameState::PuzzleSolving);
+                }
+                Err(err) => warn!("Invalid race config {:?}: {}", config, err),
+            },
+            Ok(RaceMessage::Progress {
+                player,
+                progress,
+                actions_count,
+            }) => {
+                if let Some(opponent) = client.opponents.get_mut(&player) {
+                    *opponent = RaceProgress {
+                        progress,
+                        actions_count,
+                    };
+                }
+            }
+            Ok(RaceMessage::Winner { player, .. }) => client.winner = Some(player),
+            Ok(RaceMessage::Left { player }) => {
+                client.opponents.remove(&player);
+            }
+            Ok(RaceMessage::Error { message }) => warn!("Race relay error: {}", message),
+        }
+    }
+    if disconnected {
+        warn!("Race relay disconnected");
+        commands.remove_resource::<RaceClient>();
+    }
+}
+
+// Reports the progress of the race puzzle after each action
+pub fn send_race_progress(
+    client: Res<RaceClient>,
+    puzzles: Query<&Puzzle, Changed<Puzzle>>,
+    mut solved_events: EventReader<PuzzleSolvedEvent>,
+) {
+    let Some(entity) = client.puzzle else {
+        solved_events.clear();
+        return;
+    };
+    if let Ok(puzzle) = puzzles.get(entity) {
+        client.send(&RaceRequest::Progress {
+            progress: puzzle.progress(),
+            actions_count: puzzle.actions_count,
+        });
+    }
+    for event in solved_events.read() {
+        if event.puzzle == entity {
+            client.send(&RaceRequest::Solved {
+                actions_count: event.actions_count,
+            });
+        }
+    }
+}
+
+// Rebuilt in the middle of the header when the race changes
+pub fn update_race_progress_bars(
+    mut commands: Commands,
+    client: Option<Res<RaceClient>>,
+    bars: Query<Entity, With<RaceProgressBars>>,
+    header: Query<Entity, With<UiHeader>>,
+    theme: Res<Theme>,
+    localization: Res<Localization>,
+) {
+    let Some(client) = client else {
+        for entity in bars.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    let Ok(header) = header.get_single() else {
+        return;
+    };
+    if !bars.is_empty() && !client.is_changed() && !theme.is_changed() && !localization.is_changed()
+    {
+        return;
+    }
+    for entity in bars.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let status = match client.winner {
+        Some(winner) if Some(winner) == client.player => localization.text("race.won", &[]),
+        Some(winner) => localization.text("race.winner", &[&winner]),
+        None if client.waiting => localization.text("race.waiting", &[]),
+        None => String::new(),
+    };
+    let text_style = |text: &str| TextStyle {
+        font: localization.font_for(text),
+        font_size: 16.,
+        color: theme.text,
+    };
+    let bars = commands
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(8.),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(RaceProgressBars)
+        .with_children(|parent| {
+            for (player, opponent) in client.opponents.iter() {
+                let label = localization.text("race.player", &[player]);
+                parent
+                    .spawn(TextBundle::from_section(label.clone(), text_style(&label)))
+                    .insert(ThemedText);
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(60.),
+                            height: Val::Px(8.),
+                            ..default()
+                        },
+                        background_color: theme.button_border.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100. * opponent.progress.clamp(0., 1.)),
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            background_color: theme.text.into(),
+                            ..default()
+                        });
+                    });
+            }
+            if !status.is_empty() {
+                parent
+                    .spawn(TextBundle::from_section(
+                        status.clone(),
+                        text_style(&status),
+                    ))
+                    .insert(ThemedText);
+            }
+        })
+        .id();
+    // Between the title and the score
+    commands.entity(header).insert_children(1, &[bars]);
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use bevy::window::{PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+#[cfg(not(target_family = "wasm"))]
+const SETTINGS_FILE: &str = "settings.ron";
+#[cfg(target_family = "wasm")]
+const SETTINGS_STORAGE_KEY: &str = "taquinplus.settings";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowChoice {
+    #[default]
+    Windowed,
+    Fullscreen,
+}
+
+// Preferences of the player, edited in the settings menu and saved on each change.
+// Missing fields of a saved file use their default value.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Speed of the tile actions and menu blur animations, in percent of their default speed
+    pub animation_speed_percent: u32,
+    // Blur behind the menu, from 0 for no blur to MAX_BLUR_STRENGTH
+    pub blur_strength: u32,
+    pub window: WindowChoice,
+    // Gap between neighbour tiles, in percent of the tile cell
+    pub tile_gap_percent: u32,
+    // Opacity of the action tip icons, in percent. The grid is drawn more transparent.
+    pub action_tip_alpha_percent: u32,
+    // Volume of sounds and music, in percent
+    pub volume_percent: u32,
+    // Silences sounds and music, keeping the volume for when it is unmuted
+    pub muted: bool,
+    pub music: bool,
+    // Default configuration of new puzzles
+    pub new_puzzle: NewPuzzleSettings,
+    // Badges next to the error outlines, for players who can't tell their colors apart
+    pub error_badges: bool,
+    // Name of the selected theme
+    pub theme: String,
+    // Code of the selected language
+    pub language: String,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            animation_speed_percent: 100,
+            blur_strength: MAX_BLUR_STRENGTH,
+            window: WindowChoice::Windowed,
+            tile_gap_percent: 7,
+            action_tip_alpha_percent: 30,
+            volume_percent: 100,
+            muted: false,
+            music: true,
+            new_puzzle: NewPuzzleSettings::default(),
+            error_badges: false,
+            theme: Theme::default().name,
+            language: "en".to_string(),
+        }
+    }
+}
+pub const MAX_BLUR_STRENGTH: u32 = 5;
+
+impl Settings {
+    pub fn action_animation_duration(&self) -> Duration {
+        Duration::from_millis(
+            ACTION_ANIMATION_DURATION * 100 / self.animation_speed_percent.max(1) as u64,
+        )
+    }
+    pub fn blur_animation_duration(&self) -> Duration {
+        Duration::from_millis(
+            BLUR_ANIMATION_DURATION * 100 / self.animation_speed_percent.max(1) as u64,
+        )
+    }
+    pub fn blur(&self) -> GaussianBlurSettings {
+        let kernel_size = match self.blur_strength {
+            0 => return GaussianBlurSettings::NO_BLUR,
+            1 => 7,
+            2 => 13,
+            3 => 19,
+            4 => 25,
+            _ => 31,
+        };
+        GaussianBlurSettings {
+            kernel_size,
+            sampling_distance_factor: 1.0,
+        }
+    }
+    pub fn window_mode(&self) -> WindowMode {
+        match self.window {
+            WindowChoice::Windowed => WindowMode::Windowed,
+            WindowChoice::Fullscreen => WindowMode::BorderlessFullscreen,
+        }
+    }
+    // Part of its cell covered by a tile
+    pub fn tile_occupancy(&self) -> f32 {
+        1. - self.tile_gap_percent as f32 / 100.
+    }
+    pub fn action_tip_icon_alpha(&self) -> f32 {
+        self.action_tip_alpha_percent as f32 / 100.
+    }
+    pub fn action_tip_grid_alpha(&self) -> f32 {
+        self.action_tip_icon_alpha() * 2. / 3.
+    }
+    pub fn volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.volume_percent as f32 / 100.
+        }
+    }
+    // Changes the value of a settings menu entry, theme and language excepted as
+    // they are chosen among the loaded ones
+    pub fn change(&mut self, menu_entry: MenuEntry, delta: isize) {
+        match menu_entry {
+            MenuEntry::Width | MenuEntry::Height | MenuEntry::Jigsaw | MenuEntry::Walls => {
+                self.new_puzzle.change(menu_entry, delta)
+            }
+            MenuEntry::AnimationSpeed => {
+                step(&mut self.animation_speed_percent, delta, 25, 25, 400)
+            }
+            MenuEntry::BlurStrength => {
+                step(&mut self.blur_strength, delta, 1, 0, MAX_BLUR_STRENGTH)
+            }
+            MenuEntry::WindowMode => {
+                self.window = match self.window {
+                    WindowChoice::Windowed => WindowChoice::Fullscreen,
+                    WindowChoice::Fullscreen => WindowChoice::Windowed,
+                }
+            }
+            MenuEntry::TileGap => step(&mut self.tile_gap_percent, delta, 1, 0, 20),
+            MenuEntry::ActionTipAlpha => {
+                step(&mut self.action_tip_alpha_percent, delta, 10, 0, 100)
+            }
+            MenuEntry::Volume => {
+                self.muted = false;
+                step(&mut self.volume_percent, delta, 10, 0, 100)
+            }
+            MenuEntry::Music => self.music = !self.music,
+            MenuEntry::ErrorBadges => self.error_badges = !self.error_badges,
+            _ => {}
+        }
+    }
+
+    // Saved settings, or the default ones when there are none or they can't be read
+    pub fn load() -> Self {
+        let Some(ron) = read_saved_settings() else {
+            return Settings::default();
+        };
+        ron::from_str(&ron).unwrap_or_else(|err| {
+            warn!("Ignoring invalid saved settings: {}", err);
+            Settings::default()
+        })
+    }
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(ron) => write_saved_settings(&ron),
+            Err(err) => warn!("Settings could not be saved: {}", err),
+        }
+    }
+}
+
+// Changes a value by delta steps, stopping at its bounds
+fn step(value: &mut u32, delta: isize, step: u32, min: u32, max: u32) {
+    *value = (*value as isize + delta * step as isize).clamp(min as isize, max as isize) as u32;
+}
+
+// Settings are saved in the user configuration directory
+#[cfg(not(target_family = "wasm"))]
+fn settings_path() -> Option<std::path::PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_dir.join("taquinplus").join(SETTINGS_FILE))
+}
+#[cfg(not(target_family = "wasm"))]
+fn read_saved_settings() -> Option<String> {
+    std::fs::read_to_string(settings_path()?).ok()
+}
+#[cfg(not(target_family = "wasm"))]
+fn write_saved_settings(ron: &str) {
+    let Some(path) = settings_path() else {
+        warn!("No configuration directory to save settings");
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, ron));
+    if let Err(err) = written {
+        warn!("Settings could not be saved to {}: {}", path.display(), err);
+    }
+}
+
+// Settings are saved in the browser local storage
+#[cfg(target_family = "wasm")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+#[cfg(target_family = "wasm")]
+fn read_saved_settings() -> Option<String> {
+    local_storage()?.get_item(SETTINGS_STORAGE_KEY).ok()?
+}
+#[cfg(target_family = "wasm")]
+fn write_saved_settings(ron: &str) {
+    if local_storage()
+        .and_then(|storage| storage.set_item(SETTINGS_STORAGE_KEY, ron).ok())
+        .is_none()
+    {
+        warn!("Settings could not be saved to the local storage");
+    }
+}
+
+// Applies changed settings to the theme, language and menu blur.
+// Settings of the puzzle itself are applied by apply_puzzle_settings.
+pub fn apply_settings(
+    settings: Res<Settings>,
+    themes: Res<Themes>,
+    mut theme: ResMut<Theme>,
+    mut localization: ResMut<Localization>,
+    asset_server: Res<AssetServer>,
+    mut blurs: Query<&mut GaussianBlurSettings, With<TaquinCamera>>,
+    game_state: Res<State<GameState>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if theme.name != settings.theme {
+        if let Some(selected) = themes.get(&settings.theme) {
+            *theme = selected.clone();
+        }
+    }
+    if localization.language_code() != settings.language {
+        localization.select_code(&settings.language, &asset_server);
+    }
+    // The menu is blurred, the new strength is shown right away
+    if game_state.get() == &GameState::Menu {
+        for mut blur in blurs.iter_mut() {
+            *blur = settings.blur();
+        }
+    }
+}
+
+// Applies the window mode setting, when the game owns the primary window
+pub fn apply_window_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Ok(mut window) = windows.get_single_mut() {
+        if window.mode != settings.window_mode() {
+            window.mode = settings.window_mode();
+        }
+    }
+}
+
+pub fn save_changed_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+const BUILT_IN_THEMES: [&str; 3] = [
+    include_str!("../assets/themes/dark.ron"),
+    include_str!("../assets/themes/light.ron"),
+    include_str!("../assets/themes/high_contrast.ron"),
+];
+// Directory searched for additional theme files, on native builds only
+#[cfg(not(target_family = "wasm"))]
+const CUSTOM_THEMES_DIR: &str = "themes";
+
+// Colors of the UI and of the tile outlines.
+// Theme files are RON, with colors written as hex strings like "#675B99".
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    #[serde(with = "hex_color")]
+    pub text: Color,
+    // Background of the header
+    #[serde(with = "hex_color")]
+    pub header: Color,
+    #[serde(with = "hex_color")]
+    pub button: Color,
+    // Background of the button selected in the menu
+    #[serde(with = "hex_color")]
+    pub button_active: Color,
+    #[serde(with = "hex_color")]
+    pub button_border: Color,
+    // Clear color, behind the puzzle
+    #[serde(with = "hex_color")]
+    pub background: Color,
+    #[serde(with = "hex_color")]
+    pub outline_active: Color,
+    #[serde(with = "hex_color")]
+    pub outline_misplaced: Color,
+    #[serde(with = "hex_color")]
+    pub outline_misoriented: Color,
+    #[serde(with = "hex_color")]
+    pub outline_misplaced_misoriented: Color,
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::from_ron(BUILT_IN_THEMES[0]).expect("Invalid built-in theme")
+    }
+}
+impl Theme {
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+}
+
+// All the themes that can be selected, built-in ones first
+#[derive(Resource, Debug)]
+pub struct Themes {
+    themes: Vec<Theme>,
+}
+impl Default for Themes {
+    fn default() -> Self {
+        let mut themes = BUILT_IN_THEMES
+            .iter()
+            .map(|ron| Theme::from_ron(ron).expect("Invalid built-in theme"))
+            .collect::<Vec<_>>();
+        #[cfg(not(target_family = "wasm"))]
+        themes.extend(load_custom_themes());
+        Self { themes }
+    }
+}
+impl Themes {
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes.iter().find(|theme| theme.name == name)
+    }
+    // Name of the theme after the given one, or before it for a negative delta,
+    // cycling through all themes
+    pub fn cycle(&self, name: &str, delta: isize) -> String {
+        let current = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == name)
+            .unwrap_or(0);
+        let next = (current as isize + delta).rem_euclid(self.themes.len() as isize);
+        self.themes[next as usize].name.clone()
+    }
+}
+
+// Every .ron file of the custom themes directory, sorted by file name.
+// Invalid files are reported and skipped.
+#[cfg(not(target_family = "wasm"))]
+fn load_custom_themes() -> Vec<Theme> {
+    let Ok(entries) = std::fs::read_dir(CUSTOM_THEMES_DIR) else {
+        return vec![];
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let theme = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|ron| Theme::from_ron(&ron).map_err(|err| err.to_string()));
+            match theme {
+                Ok(theme) => Some(theme),
+                Err(err) => {
+                    warn!("Ignoring theme {}: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Tag component for UI texts that use the theme text color
+#[derive(Component)]
+pub struct ThemedText;
+
+// Applies a newly selected theme to everything already spawned.
+// Menu buttons are updated by the menu itself.
+pub fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut header: Query<&mut BackgroundColor, With<UiHeader>>,
+    mut texts: Query<&mut Text, With<ThemedText>>,
+    puzzle: Query<(&Puzzle, &PuzzleAssets)>,
+    mut outlines: Query<&mut OutlineVolume>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    clear_color.0 = theme.background;
+    for mut background in header.iter_mut() {
+        *background = theme.header.into();
+    }
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.text;
+        }
+    }
+    for (puzzle, puzzle_assets) in puzzle.iter() {
+        puzzle.show_outlines(&mut outlines, puzzle_assets, &theme);
+    }
+}
+
+mod hex_color {
+    use bevy::prelude::*;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&color.to_srgba().to_hex())
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Srgba::hex(&hex)
+            .map(Color::Srgba)
+            .map_err(|err| D::Error::custom(format!("Invalid color {}: {}", hex, err)))
+    }
+}
@@ -1,43 +1,74 @@
 use crate::prelude::*;
 
-use std::f32::consts::{FRAC_PI_2, PI};
+use bevy::render::{
+    mesh::{Indices, PrimitiveTopology},
+    render_asset::RenderAssetUsages,
+};
+use std::{collections::HashMap, f32::consts::PI};
 
-#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
-pub enum CwRotation {
-    #[default]
-    R0,
-    R90,
-    R180,
-    R270,
+// Clock-wise rotation of a tile, as a number of steps in the rotation group of the tile shape.
+// Square tiles have 4 steps of 90°, hexagonal tiles have 6 steps of 60°.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CwRotation {
+    steps: u8,
+    order: u8,
+}
+impl Default for CwRotation {
+    fn default() -> Self {
+        CwRotation::R0
+    }
 }
 impl CwRotation {
-    pub fn angle(&self) -> f32 {
-        use CwRotation::*;
-        match self {
-            R0 => 0.,
-            R90 => -FRAC_PI_2,
-            R180 => PI,
-            R270 => FRAC_PI_2,
+    pub const R0: CwRotation = CwRotation { steps: 0, order: 4 };
+    // No rotation in the group of the given order
+    pub fn identity(order: u8) -> Self {
+        CwRotation { steps: 0, order }
+    }
+    // Rotation of the given number of clock-wise steps
+    pub fn from_steps(steps: u8, order: u8) -> Self {
+        CwRotation {
+            steps: steps % order,
+            order,
         }
     }
+    pub fn order(&self) -> u8 {
+        self.order
+    }
+    pub fn steps(&self) -> u8 {
+        self.steps
+    }
+    pub fn angle(&self) -> f32 {
+        -2. * PI * self.steps as f32 / self.order as f32
+    }
     pub fn rotate_cw(&self) -> Self {
-        use CwRotation::*;
-        match self {
-            R0 => R90,
-            R90 => R180,
-            R180 => R270,
-            R270 => R0,
+        CwRotation {
+            steps: (self.steps + 1) % self.order,
+            order: self.order,
         }
     }
     pub fn rotate_ccw(&self) -> Self {
-        use CwRotation::*;
-        match self {
-            R0 => R270,
-            R90 => R0,
-            R180 => R90,
-            R270 => R180,
+        CwRotation {
+            steps: (self.steps + self.order - 1) % self.order,
+            order: self.order,
+        }
+    }
+    // Rotation of the same angle, counter clock-wise
+    pub fn inverse(&self) -> Self {
+        CwRotation {
+            steps: (self.order - self.steps) % self.order,
+            order: self.order,
         }
     }
+    pub fn is_identity(&self) -> bool {
+        self.steps == 0
+    }
+    pub fn is_half_turn(&self) -> bool {
+        2 * self.steps == self.order
+    }
+    // True for rotations of 90° and 270°
+    pub fn is_quarter_turn(&self) -> bool {
+        (4 * self.steps).is_multiple_of(self.order) && (4 * self.steps / self.order) % 2 == 1
+    }
 }
 #[derive(Debug, Clone)]
 pub struct Tile {
@@ -54,16 +85,25 @@ pub struct Tile {
     pub position: Coord,
     // Defines the full size of the puzzle this tile is in.
     pub puzzle_size: Coord,
+    // Defines the shape of the puzzle this tile is in.
+    pub shape: PuzzleShape,
+    // A fixed tile, or wall, can't be moved, flipped nor rotated
+    pub fixed: bool,
+    // A double-sided tile shows the back image when it has been flipped an odd number of times
+    pub double_sided: bool,
 }
 impl Tile {
-    pub fn new(position: Coord, puzzle_size: Coord) -> Tile {
+    pub fn new(position: Coord, puzzle_size: Coord, shape: PuzzleShape) -> Tile {
         Tile {
             entity: None,
             flipped_x: false,
             flipped_y: false,
-            rotation: CwRotation::R0,
+            rotation: CwRotation::identity(shape.rotation_order()),
             position,
             puzzle_size,
+            shape,
+            fixed: false,
+            double_sided: false,
         }
     }
     pub fn compute_rotation(&self) -> Quat {
@@ -75,14 +115,89 @@ impl Tile {
             self.position,
             self.flipped_x,
             self.flipped_y,
+            self.shape,
+            self.double_sided,
         )
     }
+    pub fn set_mesh_uvs(&self, mesh: &mut Mesh) {
+        match self.shape {
+            PuzzleShape::Square => set_tile_mesh_uvs(
+                mesh,
+                self.puzzle_size,
+                self.position,
+                self.flipped_x,
+                self.flipped_y,
+                self.double_sided,
+            ),
+            PuzzleShape::Hex => set_hex_tile_mesh_uvs(
+                mesh,
+                self.puzzle_size,
+                self.position,
+                self.flipped_x,
+                self.flipped_y,
+                self.double_sided,
+            ),
+        }
+    }
+    // Flips on the local X and Y axes
+    pub fn flips(&self) -> (bool, bool) {
+        (self.flipped_x, self.flipped_y)
+    }
+    pub fn set_flips(&mut self, flipped_x: bool, flipped_y: bool) {
+        self.flipped_x = flipped_x;
+        self.flipped_y = flipped_y;
+    }
     pub fn flip_x(&mut self) {
         self.flipped_x = !self.flipped_x;
     }
     pub fn flip_y(&mut self) {
         self.flipped_y = !self.flipped_y;
     }
+    // Flips the tile along the screen axis of an ActiveFlipX or ActiveFlipY action.
+    // Returns the action on the local tile axis, used by square tiles.
+    pub fn screen_flip(&mut self, action: PuzzleAction) -> PuzzleAction {
+        use PuzzleAction::*;
+        match self.shape {
+            PuzzleShape::Square => {
+                // Convert the X/Y user axis to the local tile axis, based on tile rotation
+                let local_action = match (action, self.rotation.is_quarter_turn()) {
+                    (ActiveFlipX, true) => ActiveFlipY,
+                    (ActiveFlipY, true) => ActiveFlipX,
+                    _ => action,
+                };
+                match local_action {
+                    ActiveFlipX => self.flip_x(),
+                    ActiveFlipY => self.flip_y(),
+                    _ => panic!("Not a Flip action: {:?}", action),
+                }
+                local_action
+            }
+            PuzzleShape::Hex => {
+                // Hexagonal tiles rotate by 60° so the screen axis is not always a
+                // local axis, but flipping is also changing the rotation direction,
+                // F.R = R⁻¹.F, which allows to keep flipping on the local axis.
+                match action {
+                    ActiveFlipX => self.flip_x(),
+                    ActiveFlipY => self.flip_y(),
+                    _ => panic!("Not a Flip action: {:?}", action),
+                }
+                self.rotation = self.rotation.inverse();
+                action
+            }
+        }
+    }
+    // Applies a flip or rotation action, the flip axis being a screen axis
+    pub fn apply_orientation_action(&mut self, action: PuzzleAction) {
+        use PuzzleAction::*;
+        match action {
+            ActiveFlipX | ActiveFlipY => {
+                self.screen_flip(action);
+            }
+            ActiveRotateCW => self.rotate_cw(),
+            ActiveRotateCCW => self.rotate_ccw(),
+            _ => panic!("Not an orientation action: {:?}", action),
+        }
+    }
     pub fn is_flipped(&self) -> bool {
         !self.is_correctly_oriented() && (self.flipped_x | self.flipped_y)
     }
@@ -93,134 +208,227 @@ impl Tile {
         self.rotation = self.rotation.rotate_ccw();
     }
     pub fn is_rotated(&self) -> bool {
-        !self.is_correctly_oriented() && self.rotation != CwRotation::R0
+        !self.is_correctly_oriented() && !self.rotation.is_identity()
     }
     // True if this tile is neither flipped nor rotated.
     // This takes care of the of case of Flip X and Y and Rotated 180, which is an invariant
     pub fn is_correctly_oriented(&self) -> bool {
-        matches!(
-            (self.flipped_x, self.flipped_y, self.rotation),
-            (false, false, CwRotation::R0) | (true, true, CwRotation::R180)
-        )
+        match (self.flipped_x, self.flipped_y) {
+            (false, false) => self.rotation.is_identity(),
+            (true, true) => self.rotation.is_half_turn(),
+            _ => false,
+        }
+    }
+    // True if this double-sided tile shows its back image upright, as after a single Flip X.
+    // A single Flip Y rotated 180 is the same orientation.
+    pub fn is_back_correctly_oriented(&self) -> bool {
+        self.double_sided
+            && match (self.flipped_x, self.flipped_y) {
+                (true, false) => self.rotation.is_identity(),
+                (false, true) => self.rotation.is_half_turn(),
+                _ => false,
+            }
+    }
+}
+
+pub fn compute_tile_mesh(
+    size: Coord,
+    position: Coord,
+    flipped_x: bool,
+    flipped_y: bool,
+    shape: PuzzleShape,
+    double_sided: bool,
+) -> Mesh {
+    match shape {
+        PuzzleShape::Square => {
+            // Flat quad facing the camera. Outline normals point away from its center,
+            // so that the outline is extruded in the plane of the tile.
+            let outline_normals = QUAD_CORNERS
+                .map(|corner| corner.normalize().extend(0.).to_array())
+                .to_vec();
+            let mut mesh = Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            )
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 4])
+            .with_inserted_attribute(ATTRIBUTE_OUTLINE_NORMAL, outline_normals)
+            .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+            set_tile_mesh_position(&mut mesh);
+            set_tile_mesh_uvs(
+                &mut mesh,
+                size,
+                position,
+                flipped_x,
+                flipped_y,
+                double_sided,
+            );
+            mesh
+        }
+        PuzzleShape::Hex => {
+            compute_hex_tile_mesh(size, position, flipped_x, flipped_y, double_sided)
+        }
     }
 }
 
-pub fn compute_tile_mesh(size: Coord, position: Coord, flipped_x: bool, flipped_y: bool) -> Mesh {
-    let mut mesh = Mesh::from(Cuboid::new(1., 1., 1.));
-    set_tile_mesh_uvs(&mut mesh, size, position, flipped_x, flipped_y);
-    mesh
+// Corners of a square tile, counter clock-wise from the bottom left one
+const QUAD_CORNERS: [Vec2; 4] = [
+    Vec2::new(-0.5, -0.5),
+    Vec2::new(0.5, -0.5),
+    Vec2::new(0.5, 0.5),
+    Vec2::new(-0.5, 0.5),
+];
+
+// Maps the points of a tile, in its local [-0.5,0.5] coordinates, to the puzzle texture.
+// Each point shows the image at its location in the full puzzle, when the tile is not flipped.
+// The texture of a double-sided puzzle holds the front image on its left half
+// and the back image on its right half.
+pub struct TileUvMapping {
+    center: Vec2,
+    scale: Vec2,
+    flip: Vec2,
+    double_sided: bool,
+    shows_back: bool,
 }
+impl TileUvMapping {
+    pub fn new(
+        size: Coord,
+        position: Coord,
+        shape: PuzzleShape,
+        flipped_x: bool,
+        flipped_y: bool,
+        double_sided: bool,
+    ) -> Self {
+        // The back image is upright after a single Flip X, so it is mirrored compared to the front
+        let shows_back = double_sided && flipped_x != flipped_y;
+        Self {
+            center: tile_translation_from_position(position, size, shape).truncate(),
+            scale: match shape {
+                PuzzleShape::Square => Vec2::splat(1. / size.0.max(size.1) as f32),
+                PuzzleShape::Hex => Vec2::splat(2. * hex_tile_radius(size)),
+            },
+            flip: Vec2::new(
+                if flipped_x != shows_back { -1. } else { 1. },
+                if flipped_y { -1. } else { 1. },
+            ),
+            double_sided,
+            shows_back,
+        }
+    }
+    // UV of a local point, either on the visible side of the tile, or on its hidden side
+    pub fn uv(&self, local: Vec2, hidden_side: bool) -> [f32; 2] {
+        let point = self.center + local * self.flip * self.scale;
+        let u = point.x + 0.5;
+        let u = match (self.double_sided, self.shows_back != hidden_side) {
+            (false, _) => u,
+            (true, false) => 0.5 * u,
+            (true, true) => 0.5 + 0.5 * u,
+        };
+        [u, 0.5 - point.y]
+    }
+}
+
 pub fn set_tile_mesh_uvs(
     mesh: &mut Mesh,
     size: Coord,
     position: Coord,
     flipped_x: bool,
     flipped_y: bool,
+    double_sided: bool,
 ) {
-    let incr_x = 1.0 / (size.1 as f32);
-    let incr_y = 1.0 / (size.0 as f32);
-    let mut uv_x1 = position.1 as f32 * incr_x;
-    let mut uv_x2 = (position.1 + 1) as f32 * incr_x;
-    let mut uv_y1 = (size.0 - 1 - position.0) as f32 * incr_y;
-    let mut uv_y2 = (size.0 - position.0) as f32 * incr_y;
-    if flipped_x {
-        (uv_x1, uv_x2) = (uv_x2, uv_x1);
-    }
-    if flipped_y {
-        (uv_y1, uv_y2) = (uv_y2, uv_y1);
-    }
-    #[rustfmt::skip]
-    let uvs = vec![
-        // Assigning the UV coords for the top side.
-        [uv_x1, uv_y2], [uv_x2, uv_y2], [uv_x2, uv_y1], [uv_x1, uv_y1],
-        // Other sides are uniform color of 0,0 pixel
-        [0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
-        [0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
-        [0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
-        [0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
-        [0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
-    ];
+    let mapping = TileUvMapping::new(
+        size,
+        position,
+        PuzzleShape::Square,
+        flipped_x,
+        flipped_y,
+        double_sided,
+    );
+    let uvs = QUAD_CORNERS
+        .map(|corner| mapping.uv(corner, false))
+        .to_vec();
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
 }
 
-pub fn set_tile_mesh_position(
-    mesh: &mut Mesh,
-    x_flipping_ratio: Option<f32>,
-    y_flipping_ratio: Option<f32>,
-) {
-    let (min_x, max_x) = {
-        let val = if let Some(ratio) = x_flipping_ratio {
-            (ratio - 0.5).abs()
-        } else {
-            0.5
-        };
-        (-val, val)
-    };
-    let (min_y, max_y) = {
-        let val = if let Some(ratio) = y_flipping_ratio {
-            (ratio - 0.5).abs()
-        } else {
-            0.5
-        };
-        (-val, val)
-    };
-    let min_z = -0.5;
-    let max_z = 0.5;
-    let positions = vec![
-        // Front
-        [min_x, min_y, max_z],
-        [max_x, min_y, max_z],
-        [max_x, max_y, max_z],
-        [min_x, max_y, max_z],
-        // Back
-        [min_x, max_y, min_z],
-        [max_x, max_y, min_z],
-        [max_x, min_y, min_z],
-        [min_x, min_y, min_z],
-        // Right
-        [max_x, min_y, min_z],
-        [max_x, max_y, min_z],
-        [max_x, max_y, max_z],
-        [max_x, min_y, max_z],
-        // Left
-        [min_x, min_y, max_z],
-        [min_x, max_y, max_z],
-        [min_x, max_y, min_z],
-        [min_x, min_y, min_z],
-        // Top
-        [max_x, max_y, min_z],
-        [min_x, max_y, min_z],
-        [min_x, max_y, max_z],
-        [max_x, max_y, max_z],
-        // Bottom
-        [max_x, min_y, max_z],
-        [min_x, min_y, max_z],
-        [min_x, min_y, min_z],
-        [max_x, min_y, min_z],
-    ];
+pub fn set_tile_mesh_position(mesh: &mut Mesh) {
+    let positions = QUAD_CORNERS
+        .map(|corner| corner.extend(0.).to_array())
+        .to_vec();
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
 }
 
+// Meshes of all the tiles of a puzzle, for each position and flip.
+// They are built once and shared by all the entities showing the same tile part,
+// so that spawning and flipping tiles doesn't allocate new meshes.
+pub struct TileMeshPool {
+    meshes: HashMap<(Coord, bool, bool), Handle<Mesh>>,
+}
+impl TileMeshPool {
+    pub fn new(
+        size: Coord,
+        shape: PuzzleShape,
+        double_sided: bool,
+        jigsaw: Option<&JigsawEdges>,
+        meshes: &mut Assets<Mesh>,
+    ) -> Self {
+        let mut pool = HashMap::new();
+        for row in 0..size.0 {
+            for col in 0..size.1 {
+                for (flipped_x, flipped_y) in
+                    [(false, false), (true, false), (false, true), (true, true)]
+                {
+                    let mesh = match jigsaw {
+                        Some(jigsaw) => compute_jigsaw_tile_mesh(
+                            size,
+                            (row, col),
+                            flipped_x,
+                            flipped_y,
+                            double_sided,
+                            jigsaw.tile_sides((row, col)),
+                        ),
+                        None => compute_tile_mesh(
+                            size,
+                            (row, col),
+                            flipped_x,
+                            flipped_y,
+                            shape,
+                            double_sided,
+                        ),
+                    };
+                    pool.insert(((row, col), flipped_x, flipped_y), meshes.add(mesh));
+                }
+            }
+        }
+        Self { meshes: pool }
+    }
+    pub fn get(&self, position: Coord, flipped_x: bool, flipped_y: bool) -> Handle<Mesh> {
+        self.meshes
+            .get(&(position, flipped_x, flipped_y))
+            .expect("No mesh for tile position")
+            .clone()
+    }
+    pub fn tile_mesh(&self, tile: &Tile) -> Handle<Mesh> {
+        self.get(tile.position, tile.flipped_x, tile.flipped_y)
+    }
+}
+
+// Flip of a hexagonal tile, which needs its own mesh.
+// Square tiles are flipped by squashing their Transform, and swapping their shared mesh.
 pub struct MeshFlippingLens {
     pub tile: Tile,
-    pub flip_x: bool,
-    pub flip_y: bool,
     pub flipped: bool,
+    // The local axes along which the mesh is squashed before and after the flip,
+    // as the tile rotation changes at the middle of the animation.
+    pub axes: (Vec2, Vec2),
 }
 impl MeshFlippingLens {
-    pub fn new_flip_x(tile: Tile) -> Self {
+    // Flip of a hexagonal tile along a screen axis, from the start to the end tile rotation
+    pub fn new_hex_flip(tile: Tile, screen_axis: Vec2, start: CwRotation, end: CwRotation) -> Self {
         Self {
-            flip_x: true,
-            flip_y: false,
-            flipped: false,
-            tile,
-        }
-    }
-    pub fn new_flip_y(tile: Tile) -> Self {
-        Self {
-            flip_x: false,
-            flip_y: true,
             flipped: false,
+            axes: (
+                Vec2::from_angle(-start.angle()).rotate(screen_axis),
+                Vec2::from_angle(-end.angle()).rotate(screen_axis),
+            ),
             tile,
         }
     }
@@ -230,18 +438,138 @@ impl Lens<Mesh> for MeshFlippingLens {
     fn lerp(&mut self, target: &mut dyn Targetable<Mesh>, ratio: f32) {
         if !self.flipped && ratio > 0.5 {
             self.flipped = true;
-            set_tile_mesh_uvs(
-                target,
-                self.tile.puzzle_size,
-                self.tile.position,
-                self.tile.flipped_x,
-                self.tile.flipped_y,
-            );
+            self.tile.set_mesh_uvs(target);
         }
-        set_tile_mesh_position(
-            target,
-            self.flip_x.then_some(ratio),
-            self.flip_y.then_some(ratio),
+        let axis = if ratio > 0.5 {
+            self.axes.1
+        } else {
+            self.axes.0
+        };
+        set_hex_tile_mesh_position(target, Some((axis, (2. * ratio - 1.).abs())));
+    }
+}
+
+// Changes the tile rotation at the middle of the animation, hidden by a flip.
+pub struct RotationSnapLens {
+    pub start: Quat,
+    pub end: Quat,
+}
+impl Lens<Transform> for RotationSnapLens {
+    fn lerp(&mut self, target: &mut dyn Targetable<Transform>, ratio: f32) {
+        target.rotation = if ratio > 0.5 { self.end } else { self.start };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    // Taller than wide, so that the grid doesn't fill the unit square horizontally
+    const SIZE: Coord = (3, 2);
+    const FLIPS: [(bool, bool); 4] = [(false, false), (true, false), (false, true), (true, true)];
+
+    fn assert_uv_eq(actual: [f32; 2], expected: [f32; 2], context: &str) {
+        assert!(
+            (actual[0] - expected[0]).abs() < 1e-5 && (actual[1] - expected[1]).abs() < 1e-5,
+            "{context}: {actual:?} != {expected:?}"
         );
     }
+
+    // UVs of the corners of a tile, counter clock-wise from the bottom left one.
+    // The image covers the unit square, with V going down, and the grid is centered in it.
+    fn expected_corner_uvs(position: Coord, flipped_x: bool, flipped_y: bool) -> [[f32; 2]; 4] {
+        let cell = 1. / SIZE.0.max(SIZE.1) as f32;
+        let left = 0.5 + (position.1 as f32 - SIZE.1 as f32 / 2.) * cell;
+        let bottom = 0.5 - (position.0 as f32 - SIZE.0 as f32 / 2.) * cell;
+        let (mut u0, mut u1) = (left, left + cell);
+        let (mut v0, mut v1) = (bottom, bottom - cell);
+        if flipped_x {
+            std::mem::swap(&mut u0, &mut u1);
+        }
+        if flipped_y {
+            std::mem::swap(&mut v0, &mut v1);
+        }
+        [[u0, v0], [u1, v0], [u1, v1], [u0, v1]]
+    }
+
+    fn positions() -> impl Iterator<Item = Coord> {
+        (0..SIZE.0).flat_map(|row| (0..SIZE.1).map(move |col| (row, col)))
+    }
+
+    #[test]
+    fn uv_mapping_follows_position_and_flips() {
+        for position in positions() {
+            for (flipped_x, flipped_y) in FLIPS {
+                let mapping = TileUvMapping::new(
+                    SIZE,
+                    position,
+                    PuzzleShape::Square,
+                    flipped_x,
+                    flipped_y,
+                    false,
+                );
+                let expected = expected_corner_uvs(position, flipped_x, flipped_y);
+                for (corner, expected) in QUAD_CORNERS.iter().zip(expected) {
+                    let context = format!("{position:?} flips {flipped_x} {flipped_y}");
+                    assert_uv_eq(mapping.uv(*corner, false), expected, &context);
+                    // A single-sided tile shows the same image on both sides
+                    assert_uv_eq(mapping.uv(*corner, true), expected, &context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn double_sided_uv_mapping_shows_the_back_after_one_flip() {
+        for position in positions() {
+            for (flipped_x, flipped_y) in FLIPS {
+                let mapping = TileUvMapping::new(
+                    SIZE,
+                    position,
+                    PuzzleShape::Square,
+                    flipped_x,
+                    flipped_y,
+                    true,
+                );
+                let shows_back = flipped_x != flipped_y;
+                // The back image is mirrored, so a single Flip X shows it upright
+                let expected = expected_corner_uvs(position, flipped_x != shows_back, flipped_y);
+                for (corner, [u, v]) in QUAD_CORNERS.iter().zip(expected) {
+                    let context = format!("{position:?} flips {flipped_x} {flipped_y}");
+                    let (visible, hidden) = if shows_back {
+                        ([0.5 + 0.5 * u, v], [0.5 * u, v])
+                    } else {
+                        ([0.5 * u, v], [0.5 + 0.5 * u, v])
+                    };
+                    assert_uv_eq(mapping.uv(*corner, false), visible, &context);
+                    assert_uv_eq(mapping.uv(*corner, true), hidden, &context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tile_mesh_uvs_match_the_mapping() {
+        for position in positions() {
+            for (flipped_x, flipped_y) in FLIPS {
+                let mut mesh = Mesh::new(
+                    PrimitiveTopology::TriangleList,
+                    RenderAssetUsages::default(),
+                );
+                set_tile_mesh_uvs(&mut mesh, SIZE, position, flipped_x, flipped_y, false);
+                let Some(VertexAttributeValues::Float32x2(uvs)) =
+                    mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+                else {
+                    panic!("Tile mesh without UVs");
+                };
+                let expected = expected_corner_uvs(position, flipped_x, flipped_y);
+                assert_eq!(uvs.len(), expected.len());
+                for (uv, expected) in uvs.iter().zip(expected) {
+                    let context = format!("{position:?} flips {flipped_x} {flipped_y}");
+                    assert_uv_eq(*uv, expected, &context);
+                }
+            }
+        }
+    }
 }
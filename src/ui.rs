@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use bevy::{
     app::AppExit,
     render::{
@@ -7,18 +5,27 @@ use bevy::{
         texture::{CompressedImageFormats, ImageFormat, ImageSampler, ImageType},
     },
 };
-use rand::thread_rng;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
+#[derive(Component)]
+pub struct HudTitle;
+
 #[derive(Component)]
 pub struct HudScore;
 
-pub fn setup_ui_header(mut commands: Commands, asset_server: Res<AssetServer>) {
+// Tag component for the header bar
+#[derive(Component)]
+pub struct UiHeader;
+
+pub fn setup_ui_header(mut commands: Commands, theme: Res<Theme>, localization: Res<Localization>) {
+    let title = localization.text("title", &[]);
     let text_style = TextStyle {
-        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font: localization.font_for(&title),
         font_size: 16.,
-        color: UI_TEXT_COLOR,
+        color: theme.text,
     };
 
     commands
@@ -33,21 +40,24 @@ pub fn setup_ui_header(mut commands: Commands, asset_server: Res<AssetServer>) {
                 align_items: AlignItems::Center,
                 ..default()
             },
-            background_color: UI_COLOR_1.into(),
+            background_color: theme.header.into(),
             ..default()
         })
+        .insert(UiHeader)
         .with_children(|parent| {
-            parent.spawn(TextBundle {
-                style: Style {
-                    margin: UiRect {
-                        left: Val::Percent(1.),
+            parent
+                .spawn(TextBundle {
+                    style: Style {
+                        margin: UiRect {
+                            left: Val::Percent(1.),
+                            ..default()
+                        },
                         ..default()
                     },
+                    text: Text::from_section(title, text_style.clone()),
                     ..default()
-                },
-                text: Text::from_section("TaquinPlus", text_style.clone()),
-                ..default()
-            });
+                })
+                .insert((HudTitle, ThemedText));
             parent
                 .spawn(TextBundle {
                     style: Style {
@@ -60,19 +70,40 @@ pub fn setup_ui_header(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text::from_section("", text_style),
                     ..default()
                 })
-                .insert(HudScore);
+                .insert((HudScore, ThemedText));
         });
 }
 
 pub fn update_ui_header(
+    mut hud_titles: Query<&mut Text, (With<HudTitle>, Without<HudScore>)>,
     mut hud_scores: Query<&mut Text, With<HudScore>>,
-    puzzle: Query<&Puzzle, Changed<Puzzle>>,
+    puzzle: Query<Ref<Puzzle>, With<FocusedPuzzle>>,
+    added_focus: Query<(), Added<FocusedPuzzle>>,
+    localization: Res<Localization>,
 ) {
+    if localization.is_changed() {
+        for mut hud_title in hud_titles.iter_mut() {
+            set_localized_text(
+                &mut hud_title,
+                localization.text("title", &[]),
+                &localization,
+            );
+        }
+    }
+    // The score is the one of the focused puzzle
     if let Ok(puzzle) = puzzle.get_single() {
-        let mut hud_score = hud_scores.single_mut();
-        hud_score.sections[0].value = format!("Actions: {}", puzzle.actions_count);
+        if puzzle.is_changed() || !added_focus.is_empty() || localization.is_changed() {
+            let mut hud_score = hud_scores.single_mut();
+            let score = localization.text("hud.actions", &[&puzzle.actions_count]);
+            set_localized_text(&mut hud_score, score, &localization);
+        }
     }
 }
+// Replaces a single section text, with a font that can render it
+pub fn set_localized_text(text: &mut Text, value: String, localization: &Localization) {
+    text.sections[0].style.font = localization.font_for(&value);
+    text.sections[0].value = value;
+}
 
 #[derive(Component, Debug)]
 pub struct Menu {
@@ -84,7 +115,12 @@ impl Menu {
     pub fn new(buttons: Vec<(MenuEntry, Entity)>) -> Self {
         let escape_entry = buttons
             .iter()
-            .find(|(entry, _)| entry == &MenuEntry::Continue || entry == &MenuEntry::Show)
+            .find(|(entry, _)| {
+                matches!(
+                    entry,
+                    MenuEntry::Continue | MenuEntry::Show | MenuEntry::Back
+                )
+            })
             .map(|(entry, _)| *entry);
         Self {
             buttons,
@@ -114,40 +150,201 @@ impl Menu {
         self.escape_entry
     }
 }
+pub const MIN_PUZZLE_SIZE: usize = 2;
+pub const MAX_PUZZLE_SIZE: usize = 20;
+
+// Size and options of the next puzzle, as selected in the settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NewPuzzleSettings {
+    pub width: usize,
+    pub height: usize,
+    // Jigsaw edges, used by the square puzzle entries that support them
+    pub jigsaw: bool,
+    // Fixed tiles on the grid, one for every 16 tiles
+    pub walls: bool,
+}
+impl Default for NewPuzzleSettings {
+    fn default() -> Self {
+        Self {
+            width: 3,
+            height: 3,
+            jigsaw: false,
+            walls: false,
+        }
+    }
+}
+impl NewPuzzleSettings {
+    pub fn size(&self) -> Coord {
+        (self.height, self.width)
+    }
+    // Changes the value selected by a Width, Height, Jigsaw or Walls entry,
+    // wrapping sizes in the allowed range
+    pub fn change(&mut self, menu_entry: MenuEntry, delta: isize) {
+        let value = match menu_entry {
+            MenuEntry::Width => &mut self.width,
+            MenuEntry::Height => &mut self.height,
+            MenuEntry::Jigsaw => {
+                self.jigsaw = !self.jigsaw;
+                return;
+            }
+            MenuEntry::Walls => {
+                self.walls = !self.walls;
+                return;
+            }
+            _ => return,
+        };
+        let n_values = (MAX_PUZZLE_SIZE - MIN_PUZZLE_SIZE + 1) as isize;
+        let current = (*value).clamp(MIN_PUZZLE_SIZE, MAX_PUZZLE_SIZE);
+        *value = MIN_PUZZLE_SIZE
+            + ((current - MIN_PUZZLE_SIZE) as isize + delta).rem_euclid(n_values) as usize;
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Event, Debug)]
 pub enum MenuEntry {
     Show,
     Continue,
     NewPuzzle,
+    NewTorus,
+    NewHex,
+    NewLoopover,
+    NewDoubleSided,
+    NewExpert,
+    NewGenerated,
+    NewVersus,
+    NewOnlineRace,
+    Settings,
     Exit,
+    // Entries of the settings page
+    Width,
+    Height,
+    Jigsaw,
+    Walls,
+    AnimationSpeed,
+    BlurStrength,
+    WindowMode,
+    TileGap,
+    ActionTipAlpha,
+    Volume,
+    Music,
+    Theme,
+    ErrorBadges,
+    Language,
+    Back,
 }
 impl MenuEntry {
-    pub fn button_text(&self) -> String {
+    pub fn button_text(&self, settings: &Settings, localization: &Localization) -> String {
         use MenuEntry::*;
+        let on_off = |value: bool| localization.text(if value { "on" } else { "off" }, &[]);
         match self {
-            Show => "Show",
-            Continue => "Continue",
-            NewPuzzle => "New Puzzle",
-            Exit => "Exit",
+            Show => localization.text("menu.show", &[]),
+            Continue => localization.text("menu.continue", &[]),
+            NewPuzzle => localization.text("menu.new_puzzle", &[]),
+            NewTorus => localization.text("menu.new_torus", &[]),
+            NewHex => localization.text("menu.new_hex", &[]),
+            NewLoopover => localization.text("menu.new_loopover", &[]),
+            NewDoubleSided => localization.text("menu.new_double_sided", &[]),
+            NewExpert => localization.text("menu.new_expert", &[]),
+            NewGenerated => localization.text("menu.new_generated", &[]),
+            NewVersus => localization.text("menu.new_versus", &[]),
+            NewOnlineRace => localization.text("menu.new_online_race", &[]),
+            Settings => localization.text("menu.settings", &[]),
+            Exit => localization.text("menu.exit", &[]),
+            Width => localization.text("menu.width", &[&settings.new_puzzle.width]),
+            Height => localization.text("menu.height", &[&settings.new_puzzle.height]),
+            Jigsaw => localization.text("menu.jigsaw", &[&on_off(settings.new_puzzle.jigsaw)]),
+            Walls => localization.text("menu.walls", &[&on_off(settings.new_puzzle.walls)]),
+            AnimationSpeed => {
+                localization.text("menu.animation_speed", &[&settings.animation_speed_percent])
+            }
+            BlurStrength => localization.text("menu.blur_strength", &[&settings.blur_strength]),
+            WindowMode => {
+                let mode = match settings.window {
+                    WindowChoice::Windowed => localization.text("window.windowed", &[]),
+                    WindowChoice::Fullscreen => localization.text("window.fullscreen", &[]),
+                };
+                localization.text("menu.window_mode", &[&mode])
+            }
+            TileGap => localization.text("menu.tile_gap", &[&settings.tile_gap_percent]),
+            ActionTipAlpha => localization.text(
+                "menu.action_tip_alpha",
+                &[&settings.action_tip_alpha_percent],
+            ),
+            Volume if settings.muted => localization.text("menu.volume_muted", &[]),
+            Volume => localization.text("menu.volume", &[&settings.volume_percent]),
+            Music => localization.text("menu.music", &[&on_off(settings.music)]),
+            // Custom themes have no translation of their name
+            Theme => {
+                let theme_key = format!("theme.{}", settings.theme);
+                let theme_name = localization
+                    .get(&theme_key)
+                    .unwrap_or(settings.theme.as_str());
+                localization.text("menu.theme", &[&theme_name])
+            }
+            ErrorBadges => {
+                localization.text("menu.error_badges", &[&on_off(settings.error_badges)])
+            }
+            Language => localization.text("menu.language", &[&localization.language_name()]),
+            Back => localization.text("menu.back", &[]),
         }
-        .to_string()
     }
 }
 
-pub fn setup_menu(mut commands: Commands, puzzle: Query<&Puzzle>) {
+pub fn setup_menu(
+    mut commands: Commands,
+    puzzle: Query<&Puzzle>,
+    menu_page: Res<State<MenuPage>>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    localization: Res<Localization>,
+    race_client: Option<Res<RaceClient>>,
+) {
     let mut button_entries = vec![];
-    let puzzle = puzzle.get_single();
-
-    if puzzle.is_ok() {
-        button_entries.push(if puzzle.as_ref().is_ok_and(|puzzle| puzzle.is_solved) {
-            MenuEntry::Show
-        } else {
-            MenuEntry::Continue
-        });
+    match menu_page.get() {
+        MenuPage::Main => {
+            if !puzzle.is_empty() {
+                button_entries.push(if puzzle.iter().all(|puzzle| puzzle.is_solved) {
+                    MenuEntry::Show
+                } else {
+                    MenuEntry::Continue
+                });
+            }
+            button_entries.push(MenuEntry::NewPuzzle);
+            button_entries.push(MenuEntry::NewTorus);
+            button_entries.push(MenuEntry::NewHex);
+            button_entries.push(MenuEntry::NewLoopover);
+            button_entries.push(MenuEntry::NewDoubleSided);
+            button_entries.push(MenuEntry::NewExpert);
+            button_entries.push(MenuEntry::NewGenerated);
+            button_entries.push(MenuEntry::NewVersus);
+            // Online races need the relay given on the command line
+            if race_client.is_some() {
+                button_entries.push(MenuEntry::NewOnlineRace);
+            }
+            button_entries.push(MenuEntry::Settings);
+            #[cfg(not(target_family = "wasm"))]
+            button_entries.push(MenuEntry::Exit);
+        }
+        MenuPage::Settings => {
+            button_entries.push(MenuEntry::Width);
+            button_entries.push(MenuEntry::Height);
+            button_entries.push(MenuEntry::Jigsaw);
+            button_entries.push(MenuEntry::Walls);
+            button_entries.push(MenuEntry::AnimationSpeed);
+            button_entries.push(MenuEntry::BlurStrength);
+            #[cfg(not(target_family = "wasm"))]
+            button_entries.push(MenuEntry::WindowMode);
+            button_entries.push(MenuEntry::TileGap);
+            button_entries.push(MenuEntry::ActionTipAlpha);
+            button_entries.push(MenuEntry::Volume);
+            button_entries.push(MenuEntry::Music);
+            button_entries.push(MenuEntry::Theme);
+            button_entries.push(MenuEntry::ErrorBadges);
+            button_entries.push(MenuEntry::Language);
+            button_entries.push(MenuEntry::Back);
+        }
     }
-    button_entries.push(MenuEntry::NewPuzzle);
-    #[cfg(not(target_family = "wasm"))]
-    button_entries.push(MenuEntry::Exit);
     let buttons = button_entries
         .into_iter()
         .map(|button_entry| {
@@ -155,29 +352,32 @@ pub fn setup_menu(mut commands: Commands, puzzle: Query<&Puzzle>) {
                 .spawn(ButtonBundle {
                     style: Style {
                         width: Val::Percent(33.),
-                        height: Val::Px(40.),
+                        height: Val::Px(32.),
                         // horizontally center child text
                         justify_content: JustifyContent::Center,
                         // vertically center child text
                         align_items: AlignItems::Center,
                         border: UiRect::all(Val::Px(4.)),
-                        margin: UiRect::all(Val::Px(12.)),
+                        margin: UiRect::all(Val::Px(3.)),
                         ..default()
                     },
-                    background_color: UI_COLOR_3.into(),
-                    border_color: UI_COLOR_1.into(),
+                    background_color: theme.button.into(),
+                    border_color: theme.button_border.into(),
                     ..default()
                 })
                 .insert(button_entry)
                 .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        button_entry.button_text(),
-                        TextStyle {
-                            font_size: 32.0,
-                            color: UI_TEXT_COLOR,
-                            ..default()
-                        },
-                    ));
+                    let text = button_entry.button_text(&settings, &localization);
+                    parent
+                        .spawn(TextBundle::from_section(
+                            text.clone(),
+                            TextStyle {
+                                font: localization.font_for(&text),
+                                font_size: 26.0,
+                                color: theme.text,
+                            },
+                        ))
+                        .insert(ThemedText);
                 })
                 .id();
             (button_entry, entity)
@@ -215,28 +415,57 @@ pub fn despawn_menu(mut commands: Commands, menu: Query<Entity, With<Menu>>) {
 }
 
 pub fn menu_active_update(
-    menu: Query<&Menu, Changed<Menu>>,
-    mut backgrounds: Query<&mut BackgroundColor>,
+    menu: Query<Ref<Menu>>,
+    mut buttons: Query<(&mut BackgroundColor, &mut BorderColor)>,
+    theme: Res<Theme>,
 ) {
     if let Ok(menu) = menu.get_single() {
+        if !menu.is_changed() && !theme.is_changed() {
+            return;
+        }
         for (i, (_, entity)) in menu.buttons.iter().enumerate() {
-            let mut color = backgrounds
+            let (mut color, mut border_color) = buttons
                 .get_mut(*entity)
                 .expect("Inconsistency in menu buttons Entity and spawned entities");
             *color = if i == menu.active {
-                UI_COLOR_2.into()
+                theme.button_active.into()
             } else {
-                UI_COLOR_3.into()
+                theme.button.into()
             };
+            *border_color = theme.button_border.into();
+        }
+    }
+}
+// Updates the text of the settings entries when the selected settings change
+pub fn menu_settings_update(
+    settings: Res<Settings>,
+    localization: Res<Localization>,
+    buttons: Query<(&MenuEntry, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    // A new language changes the text of every entry
+    if settings.is_changed() || localization.is_changed() {
+        for (menu_entry, children) in buttons.iter() {
+            for child in children.iter() {
+                if let Ok(mut text) = texts.get_mut(*child) {
+                    let value = menu_entry.button_text(&settings, &localization);
+                    set_localized_text(&mut text, value, &localization);
+                }
+            }
         }
     }
 }
+
 pub fn menu_interaction(
     mut menu: Query<&mut Menu>,
-    button_interaction: Query<(&Interaction, &MenuEntry)>,
+    button_interaction: Query<(&Interaction, &MenuEntry), Changed<Interaction>>,
     input: Res<ButtonInput<KeyCode>>,
     mouse_button: Res<ButtonInput<MouseButton>>,
     mut menu_events: EventWriter<MenuEntry>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut settings: ResMut<Settings>,
+    themes: Res<Themes>,
+    localization: Res<Localization>,
 ) {
     for (interaction, menu_entry) in button_interaction.iter() {
         let mut menu = menu.single_mut();
@@ -246,15 +475,28 @@ pub fn menu_interaction(
             }
             Interaction::Hovered => {
                 menu.set_active(menu_entry);
+                sound_effects.send(SoundEffect::MenuMove);
             }
             Interaction::None => {}
         }
     }
     if input.just_pressed(KeyCode::ArrowDown) {
         menu.single_mut().set_next_active();
+        sound_effects.send(SoundEffect::MenuMove);
     }
     if input.just_pressed(KeyCode::ArrowUp) {
         menu.single_mut().set_prev_active();
+        sound_effects.send(SoundEffect::MenuMove);
+    }
+    for (key, delta) in [(KeyCode::ArrowLeft, -1), (KeyCode::ArrowRight, 1)] {
+        if input.just_pressed(key) {
+            sound_effects.send(SoundEffect::MenuMove);
+            match menu.single().get_active_entry() {
+                MenuEntry::Theme => settings.theme = themes.cycle(&settings.theme, delta),
+                MenuEntry::Language => settings.language = localization.cycle(delta),
+                menu_entry => settings.change(menu_entry, delta),
+            }
+        }
     }
     if input.just_pressed(KeyCode::Enter) || input.just_pressed(KeyCode::Space) {
         menu_events.send(menu.single().get_active_entry());
@@ -271,11 +513,18 @@ pub fn menu_event_handler(
     mut menu_events: EventReader<MenuEntry>,
     mut app_exit_events: EventWriter<AppExit>,
     mut next_gamestate: ResMut<NextState<GameState>>,
+    mut next_menu_page: ResMut<NextState<MenuPage>>,
+    mut sound_effects: EventWriter<SoundEffect>,
     asset_server: Res<AssetServer>,
     mut images: ResMut<Assets<Image>>,
-    puzzle: Query<(Entity, &Puzzle)>,
+    mut settings: ResMut<Settings>,
+    themes: Res<Themes>,
+    localization: Res<Localization>,
+    puzzle: Query<Entity, With<Puzzle>>,
+    mut race_client: Option<ResMut<RaceClient>>,
 ) {
     for menu_entry in menu_events.read() {
+        sound_effects.send(SoundEffect::MenuSelect);
         match menu_entry {
             MenuEntry::Continue => {
                 next_gamestate.set(GameState::PuzzleSolving);
@@ -283,88 +532,389 @@ pub fn menu_event_handler(
             MenuEntry::Show => {
                 next_gamestate.set(GameState::PuzzleSolved);
             }
-            MenuEntry::NewPuzzle => {
-                let new_size = if let Ok((entity, puzzle)) = puzzle.get_single() {
-                    let cur_size = puzzle.size();
+            MenuEntry::Settings => {
+                next_menu_page.set(MenuPage::Settings);
+            }
+            MenuEntry::Back => {
+                next_menu_page.set(MenuPage::Main);
+            }
+            MenuEntry::Theme => {
+                settings.theme = themes.cycle(&settings.theme, 1);
+            }
+            MenuEntry::Language => {
+                settings.language = localization.cycle(1);
+            }
+            // The volume is changed with the arrows
+            MenuEntry::Volume => {
+                settings.muted = !settings.muted;
+            }
+            MenuEntry::Width
+            | MenuEntry::Height
+            | MenuEntry::Jigsaw
+            | MenuEntry::Walls
+            | MenuEntry::AnimationSpeed
+            | MenuEntry::BlurStrength
+            | MenuEntry::WindowMode
+            | MenuEntry::TileGap
+            | MenuEntry::ActionTipAlpha
+            | MenuEntry::Music
+            | MenuEntry::ErrorBadges => {
+                settings.change(*menu_entry, 1);
+            }
+            MenuEntry::NewPuzzle
+            | MenuEntry::NewTorus
+            | MenuEntry::NewHex
+            | MenuEntry::NewLoopover
+            | MenuEntry::NewDoubleSided
+            | MenuEntry::NewExpert
+            | MenuEntry::NewGenerated => {
+                for entity in puzzle.iter() {
                     commands.entity(entity).despawn_recursive();
-                    if cur_size == (7, 7) {
-                        (3, 3)
-                    } else {
-                        (cur_size.0 + 1, cur_size.1 + 1)
-                    }
-                } else {
-                    (3, 3)
-                };
-                let image = if new_size != (3, 3) {
-                    #[cfg(not(target_family = "wasm"))]
-                    {
-                        println!("FETCH any image from web");
-                        if let Ok(bytes) = attohttpc::get("https://picsum.photos/1024.webp")
-                            .send()
-                            .and_then(|resp| resp.bytes())
-                        {
-                            images.add(
-                                Image::from_buffer(
-                                    &bytes,
-                                    ImageType::Format(ImageFormat::WebP),
-                                    CompressedImageFormats::NONE,
-                                    true,
-                                    ImageSampler::Default.clone(),
-                                    RenderAssetUsages::all(),
-                                )
-                                .expect("Image could not be loaded"),
-                            )
-                        } else {
-                            asset_server.load("images/1.png")
-                        }
-                    }
-                    #[cfg(target_family = "wasm")]
-                    asset_server.load("images/1.png")
-                } else {
-                    asset_server.load("images/1.png")
+                }
+                let variant = match menu_entry {
+                    MenuEntry::NewTorus => PuzzleVariant::Torus,
+                    MenuEntry::NewHex => PuzzleVariant::Hex,
+                    MenuEntry::NewLoopover => PuzzleVariant::Loopover,
+                    MenuEntry::NewDoubleSided => PuzzleVariant::DoubleSided,
+                    MenuEntry::NewExpert => PuzzleVariant::Expert,
+                    MenuEntry::NewGenerated => PuzzleVariant::Generated,
+                    _ => PuzzleVariant::Classic,
                 };
-                let random_hole = !matches!(new_size, (3, 3) | (4, 4));
-                let mut rng = thread_rng();
-                let mut puzzle = Puzzle::new(image, new_size.0, new_size.1, random_hole, &mut rng);
-                let (n_moves, flip_pct, rot_pct) = match new_size.0 {
-                    3 => (5, 0., 0.),
-                    4 => (20, 0., 0.),
-                    5 => (100, 0.0, 0.2),
-                    _ => (1000, 1., 1.),
-                };
-                puzzle.shuffle(n_moves, flip_pct, rot_pct, rng);
-                // Spawn a simple Entity with just a Puzzle
-                // All addition entities will be added in a dedicated system
-                commands.spawn(puzzle);
+                let options = NewPuzzleOptions::new(variant, &settings.new_puzzle);
+                spawn_new_puzzle(&mut commands, &mut images, &asset_server, &options);
+                next_gamestate.set(GameState::PuzzleSolving);
+            }
+            MenuEntry::NewVersus => {
+                for entity in puzzle.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                start_versus_race(
+                    &mut commands,
+                    &mut images,
+                    &asset_server,
+                    &settings.new_puzzle,
+                );
                 next_gamestate.set(GameState::PuzzleSolving);
             }
+            // The race puzzle replaces the current ones once the relay starts the race
+            MenuEntry::NewOnlineRace => {
+                if let Some(race_client) = race_client.as_mut() {
+                    race_client.join();
+                }
+            }
             MenuEntry::Exit => {
                 app_exit_events.send(AppExit::Success);
             }
         }
     }
 }
+// Kind of puzzle created by each New entry of the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PuzzleVariant {
+    Classic,
+    Torus,
+    Hex,
+    Loopover,
+    DoubleSided,
+    // Double-sided puzzle solved once both of its images are complete
+    Expert,
+    // Offline puzzle with a procedural image
+    Generated,
+}
+impl PuzzleVariant {
+    pub fn is_double_sided(&self) -> bool {
+        matches!(self, PuzzleVariant::DoubleSided | PuzzleVariant::Expert)
+    }
+}
+
+// Kinds of actions used to scramble a new puzzle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrambleActions {
+    pub slide: bool,
+    pub rotate: bool,
+    pub flip: bool,
+}
+
+// Everything needed to create a new puzzle, from the menu or the command line.
+// Options left to None are chosen from the number of tiles.
+#[derive(Debug, Clone)]
+pub struct NewPuzzleOptions {
+    pub variant: PuzzleVariant,
+    pub size: Coord,
+    pub jigsaw: bool,
+    pub walls: bool,
+    // Seed of the scramble and of the web image, to get the same puzzle again
+    pub seed: Option<u64>,
+    // Image file used instead of the default or web image
+    pub image_path: Option<std::path::PathBuf>,
+    pub scramble: Option<ScrambleActions>,
+    pub random_hole: Option<bool>,
+    // Puzzle state in the text format, used instead of a scramble
+    pub state: Option<String>,
+}
+impl NewPuzzleOptions {
+    pub fn new(variant: PuzzleVariant, new_puzzle: &NewPuzzleSettings) -> Self {
+        Self {
+            variant,
+            size: new_puzzle.size(),
+            jigsaw: new_puzzle.jigsaw,
+            walls: new_puzzle.walls,
+            seed: None,
+            image_path: None,
+            scramble: None,
+            random_hole: None,
+            state: None,
+        }
+    }
+    pub fn shape(&self) -> PuzzleShape {
+        if self.variant == PuzzleVariant::Hex {
+            PuzzleShape::Hex
+        } else {
+            PuzzleShape::Square
+        }
+    }
+    pub fn config(&self) -> PuzzleConfig {
+        let n_tiles = self.size.0 * self.size.1;
+        let shape = self.shape();
+        let double_sided = self.variant.is_double_sided();
+        let kind = if self.variant == PuzzleVariant::Loopover {
+            PuzzleKind::Loopover
+        } else {
+            PuzzleKind::Taquin
+        };
+        // Larger grids get more holes, to keep them less tedious
+        let n_holes = match n_tiles {
+            0..=35 => 1,
+            _ => 2 + n_tiles / 100,
+        };
+        let n_walls = if self.walls { n_tiles / 16 } else { 0 };
+        PuzzleConfig {
+            width: self.size.1,
+            height: self.size.0,
+            kind,
+            shape,
+            random_hole: self.random_hole.unwrap_or(n_tiles > 16),
+            wrapping: self.variant == PuzzleVariant::Torus,
+            n_holes,
+            n_walls,
+            double_sided,
+            expert: self.variant == PuzzleVariant::Expert,
+            // Tabs would not match between the two images of a double-sided tile
+            jigsaw: self.jigsaw && shape == PuzzleShape::Square && !double_sided,
+        }
+    }
+    // New scrambled puzzle showing the given image.
+    // Difficulty grows with the number of tiles.
+    pub fn create_puzzle(&self, image: Handle<Image>) -> Puzzle {
+        if let Some(state) = &self.state {
+            return Puzzle::from_text(state, image).expect("Invalid state");
+        }
+        let config = self.config();
+        let n_tiles = self.size.0 * self.size.1;
+        let mut rng = self
+            .seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        let mut puzzle = Puzzle::new(image, &config, &mut rng);
+        let (n_moves, flip_pct, rot_pct) = match n_tiles {
+            0..=9 => (5, 0., 0.),
+            10..=16 => (20, 0., 0.),
+            17..=25 => (100, 0.0, 0.2),
+            _ => (1000.max(10 * n_tiles), 1., 1.),
+        };
+        // Double-sided puzzles always start with some tiles showing their back
+        let flip_pct = if config.double_sided {
+            f64::max(flip_pct, 0.5)
+        } else {
+            flip_pct
+        };
+        match self.scramble {
+            None => puzzle.shuffle(n_moves, flip_pct, rot_pct, rng),
+            Some(scramble) => {
+                // Requested actions are always used, even on the small grids that don't by default
+                let flip_pct = if scramble.flip {
+                    f64::max(flip_pct, 0.5)
+                } else {
+                    0.
+                };
+                let rot_pct = if scramble.rotate {
+                    rot_pct.max(0.5)
+                } else {
+                    0.
+                };
+                if scramble.slide {
+                    puzzle.shuffle(n_moves, flip_pct, rot_pct, rng);
+                } else {
+                    puzzle.scramble_orientations(flip_pct, rot_pct, rng);
+                }
+            }
+        }
+        puzzle
+    }
+}
+
+// Spawns the Puzzle entity of a new scrambled puzzle.
+// All additional entities will be added in a dedicated system.
+pub fn spawn_new_puzzle(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    asset_server: &AssetServer,
+    options: &NewPuzzleOptions,
+) -> Entity {
+    spawn_new_puzzles(commands, images, asset_server, options, 1)[0]
+}
+
+// Spawns several puzzles showing the same image, with the same scramble when the options
+// have a seed.
+pub fn spawn_new_puzzles(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    asset_server: &AssetServer,
+    options: &NewPuzzleOptions,
+    count: usize,
+) -> Vec<Entity> {
+    let new_size = options.size;
+    let n_tiles = new_size.0 * new_size.1;
+    let shape = options.shape();
+    let image_file = options
+        .image_path
+        .as_ref()
+        .and_then(|path| load_image_file(path, images));
+    let image = if let Some(image) = image_file {
+        image
+    } else if options.variant == PuzzleVariant::Generated {
+        // Numbers for the first sizes, then the checkerboard when
+        // tiles start to be rotated and flipped.
+        let procedural_image = match n_tiles {
+            0..=16 => ProceduralImage::Numbered,
+            17..=25 => ProceduralImage::Checkerboard,
+            _ => ProceduralImage::Gradient,
+        };
+        images.add(procedural_image.generate(new_size, shape))
+    } else {
+        load_puzzle_image(new_size, options.seed, images, |_| {
+            asset_server.load("images/1.png")
+        })
+    };
+    // The texture of a double-sided puzzle is composed once both images are loaded
+    let mut double_sided_image = options.variant.is_double_sided().then(|| DoubleSidedImage {
+        front: image.clone(),
+        back: load_puzzle_image(
+            new_size,
+            options.seed.map(|seed| seed.wrapping_add(1)),
+            images,
+            |images| images.add(ProceduralImage::Gradient.generate(new_size, shape)),
+        ),
+        texture: images.reserve_handle(),
+    });
+    let image = double_sided_image
+        .as_ref()
+        .map_or(image, |double_sided_image| {
+            double_sided_image.texture.clone()
+        });
+    (0..count)
+        .map(|_| {
+            let puzzle = options.create_puzzle(image.clone());
+            info!("New puzzle {}", puzzle.to_text());
+            let mut puzzle_entity = commands.spawn(puzzle);
+            // The shared texture is composed once, with the first puzzle
+            if let Some(double_sided_image) = double_sided_image.take() {
+                puzzle_entity.insert(double_sided_image);
+            }
+            puzzle_entity.id()
+        })
+        .collect()
+}
+// Image given on the command line, None when it can't be read
+fn load_image_file(path: &std::path::Path, images: &mut Assets<Image>) -> Option<Handle<Image>> {
+    let extension = path.extension()?.to_str()?;
+    let image = std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| {
+            Image::from_buffer(
+                &bytes,
+                ImageType::Extension(extension),
+                CompressedImageFormats::NONE,
+                true,
+                ImageSampler::Default.clone(),
+                RenderAssetUsages::all(),
+            )
+            .map_err(|err| err.to_string())
+        });
+    match image {
+        Ok(image) => Some(images.add(image)),
+        Err(err) => {
+            warn!("Ignoring image {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+// Larger puzzles use a random image from the web, small ones and failed fetches the fallback.
+// A seeded puzzle always gets the same web image.
+fn load_puzzle_image(
+    size: Coord,
+    seed: Option<u64>,
+    images: &mut Assets<Image>,
+    fallback: impl FnOnce(&mut Assets<Image>) -> Handle<Image>,
+) -> Handle<Image> {
+    if size.0 * size.1 > 9 {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            info!("FETCH any image from web");
+            let url = match seed {
+                Some(seed) => format!("https://picsum.photos/seed/{}/1024.webp", seed),
+                None => "https://picsum.photos/1024.webp".to_string(),
+            };
+            if let Ok(bytes) = attohttpc::get(url).send().and_then(|resp| resp.bytes()) {
+                images.add(
+                    Image::from_buffer(
+                        &bytes,
+                        ImageType::Format(ImageFormat::WebP),
+                        CompressedImageFormats::NONE,
+                        true,
+                        ImageSampler::Default.clone(),
+                        RenderAssetUsages::all(),
+                    )
+                    .expect("Image could not be loaded"),
+                )
+            } else {
+                fallback(images)
+            }
+        }
+        #[cfg(target_family = "wasm")]
+        {
+            let _ = seed;
+            fallback(images)
+        }
+    } else {
+        fallback(images)
+    }
+}
+
 pub fn puzzle_deblur(
     mut commands: Commands,
-    camera: Query<Entity, (With<Camera>, With<GaussianBlurSettings>)>,
+    camera: Query<Entity, (With<TaquinCamera>, With<GaussianBlurSettings>)>,
+    settings: Res<Settings>,
 ) {
-    let tween = Tween::new(
-        EaseFunction::QuadraticInOut,
-        Duration::from_millis(BLUR_ANIMATION_DURATION),
-        GaussianBlurLens::new(BLUR, GaussianBlurSettings::NO_BLUR),
-    );
-    let camera_entity = camera.single();
-    commands.entity(camera_entity).insert(Animator::new(tween));
+    for camera_entity in camera.iter() {
+        let tween = Tween::new(
+            EaseFunction::QuadraticInOut,
+            settings.blur_animation_duration(),
+            GaussianBlurLens::new(settings.blur(), GaussianBlurSettings::NO_BLUR),
+        );
+        commands.entity(camera_entity).insert(Animator::new(tween));
+    }
 }
 pub fn puzzle_blur(
     mut commands: Commands,
-    camera: Query<Entity, (With<Camera>, With<GaussianBlurSettings>)>,
+    camera: Query<Entity, (With<TaquinCamera>, With<GaussianBlurSettings>)>,
+    settings: Res<Settings>,
 ) {
-    let tween = Tween::new(
-        EaseFunction::QuadraticInOut,
-        Duration::from_millis(BLUR_ANIMATION_DURATION),
-        GaussianBlurLens::new(GaussianBlurSettings::NO_BLUR, BLUR),
-    );
-    let camera_entity = camera.single();
-    commands.entity(camera_entity).insert(Animator::new(tween));
+    for camera_entity in camera.iter() {
+        let tween = Tween::new(
+            EaseFunction::QuadraticInOut,
+            settings.blur_animation_duration(),
+            GaussianBlurLens::new(GaussianBlurSettings::NO_BLUR, settings.blur()),
+        );
+        commands.entity(camera_entity).insert(Animator::new(tween));
+    }
 }
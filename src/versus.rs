@@ -0,0 +1,377 @@
+use std::hash::Hash;
+use std::time::Duration;
+
+use bevy::time::Stopwatch;
+
+use crate::prelude::*;
+
+// Local split-screen race: two players solve the same scramble side by side, and the first
+// one to solve their board wins. Each player has their own controls:
+//   Left board, keyboard set A: W A S D slide, Left Shift + W A S D move the selection,
+//     Q E rotate, Z C flip, Tab selects the next hole
+//   Right board, keyboard set B: arrows slide, Right Shift + arrows move the selection,
+//     , . rotate, ; ' flip, / selects the next hole
+//   Right board, any gamepad: D-pad slides, left bumper + D-pad moves the selection,
+//     West East rotate, South North flip, right bumper selects the next hole
+pub const VERSUS_PLAYERS: usize = 2;
+
+// Board of a player in a versus race, 0 being the left one
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersusPlayer(pub usize);
+
+#[derive(Resource, Default)]
+pub struct VersusRace {
+    // Time spent racing, paused while the menu is shown
+    pub stopwatch: Stopwatch,
+    // Race time at which each player solved their board
+    pub solved_times: [Option<Duration>; VERSUS_PLAYERS],
+    pub winner: Option<usize>,
+}
+
+// Line below the header with the scores of the players
+#[derive(Component)]
+pub struct VersusScores;
+
+// Text with the action count and time of a player
+#[derive(Component)]
+pub struct VersusScore(usize);
+
+// Results shown once the race is won
+#[derive(Component)]
+pub struct VersusResults;
+
+// Buttons of the controls of a player
+struct VersusButtons<T> {
+    // Up, down, left and right
+    directions: [T; 4],
+    // Held to move the selection instead of sliding
+    selection: T,
+    // Counter clock-wise and clock-wise
+    rotations: [T; 2],
+    // On the X and Y axes
+    flips: [T; 2],
+    next_hole: T,
+}
+
+const KEYBOARD_BUTTONS: [VersusButtons<KeyCode>; VERSUS_PLAYERS] = [
+    VersusButtons {
+        directions: [KeyCode::KeyW, KeyCode::KeyS, KeyCode::KeyA, KeyCode::KeyD],
+        selection: KeyCode::ShiftLeft,
+        rotations: [KeyCode::KeyQ, KeyCode::KeyE],
+        flips: [KeyCode::KeyZ, KeyCode::KeyC],
+        next_hole: KeyCode::Tab,
+    },
+    VersusButtons {
+        directions: [
+            KeyCode::ArrowUp,
+            KeyCode::ArrowDown,
+            KeyCode::ArrowLeft,
+            KeyCode::ArrowRight,
+        ],
+        selection: KeyCode::ShiftRight,
+        rotations: [KeyCode::Comma, KeyCode::Period],
+        flips: [KeyCode::Semicolon, KeyCode::Quote],
+        next_hole: KeyCode::Slash,
+    },
+];
+
+fn gamepad_buttons(gamepad: Gamepad) -> VersusButtons<GamepadButton> {
+    let button = |button_type| GamepadButton::new(gamepad, button_type);
+    VersusButtons {
+        directions: [
+            button(GamepadButtonType::DPadUp),
+            button(GamepadButtonType::DPadDown),
+            button(GamepadButtonType::DPadLeft),
+            button(GamepadButtonType::DPadRight),
+        ],
+        selection: button(GamepadButtonType::LeftTrigger),
+        rotations: [
+            button(GamepadButtonType::West),
+            button(GamepadButtonType::East),
+        ],
+        flips: [
+            button(GamepadButtonType::South),
+            button(GamepadButtonType::North),
+        ],
+        next_hole: button(GamepadButtonType::RightTrigger),
+    }
+}
+
+// Actions of the buttons just pressed
+fn read_actions<T: Copy + Eq + Hash + Send + Sync + 'static>(
+    input: &ButtonInput<T>,
+    buttons: &VersusButtons<T>,
+) -> Vec<PuzzleAction> {
+    use PuzzleAction::*;
+    let direction_actions = if input.pressed(buttons.selection) {
+        [
+            MoveActiveUp,
+            MoveActiveDown,
+            MoveActiveLeft,
+            MoveActiveRight,
+        ]
+    } else {
+        [MoveUp, MoveDown, MoveLeft, MoveRight]
+    };
+    buttons
+        .directions
+        .into_iter()
+        .zip(direction_actions)
+        .chain(
+            buttons
+                .rotations
+                .into_iter()
+                .zip([ActiveRotateCCW, ActiveRotateCW]),
+        )
+        .chain(buttons.flips.into_iter().zip([ActiveFlipX, ActiveFlipY]))
+        .chain([(buttons.next_hole, NextHole)])
+        .filter(|(button, _)| input.just_pressed(*button))
+        .map(|(_, action)| action)
+        .collect()
+}
+
+// Replaces the puzzles with the boards of a new race, both with the same scramble
+pub fn start_versus_race(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    asset_server: &AssetServer,
+    new_puzzle: &NewPuzzleSettings,
+) {
+    let mut options = NewPuzzleOptions::new(PuzzleVariant::Classic, new_puzzle);
+    options.seed = Some(rand::random());
+    let boards = spawn_new_puzzles(commands, images, asset_server, &options, VERSUS_PLAYERS);
+    for (player, entity) in boards.into_iter().enumerate() {
+        commands
+            .entity(entity)
+            .insert((VersusPlayer(player), PuzzleSlot(player)));
+    }
+    commands.insert_resource(VersusRace::default());
+}
+
+pub fn versus_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    players: Query<(Entity, &VersusPlayer)>,
+    race: Res<VersusRace>,
+    mut puzzle_action_events: EventWriter<PuzzleActionEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // Back from the menu after the race, the results are shown again
+    if race.winner.is_some() {
+        next_state.set(GameState::PuzzleSolved);
+        return;
+    }
+    for (entity, player) in players.iter() {
+        let mut actions = read_actions(&keyboard, &KEYBOARD_BUTTONS[player.0]);
+        if player.0 == 1 {
+            for gamepad in gamepads.iter() {
+                actions.extend(read_actions(&gamepad_input, &gamepad_buttons(gamepad)));
+            }
+        }
+        puzzle_action_events.send_batch(actions.into_iter().map(|action| PuzzleActionEvent {
+            puzzle: entity,
+            action,
+        }));
+    }
+    if keyboard.just_pressed(KeyCode::Escape) || mouse_button.just_pressed(MouseButton::Right) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+pub fn tick_versus_race(time: Res<Time>, mut race: ResMut<VersusRace>) {
+    if race.winner.is_none() {
+        race.stopwatch.tick(time.delta());
+    }
+}
+
+// The first board solved wins the race
+pub fn finish_versus_race(
+    mut solved_events: EventReader<PuzzleSolvedEvent>,
+    players: Query<&VersusPlayer>,
+    mut race: ResMut<VersusRace>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in solved_events.read() {
+        let Ok(player) = players.get(event.puzzle) else {
+            continue;
+        };
+        let time = race.stopwatch.elapsed();
+        race.solved_times[player.0].get_or_insert(time);
+        if race.winner.is_none() {
+            race.winner = Some(player.0);
+            next_state.set(GameState::PuzzleSolved);
+        }
+    }
+}
+
+// Ends the race once its boards are gone, like when a new puzzle is started
+pub fn end_versus_race(
+    mut commands: Commands,
+    players: Query<(), With<VersusPlayer>>,
+    versus_ui: Query<Entity, Or<(With<VersusScores>, With<VersusResults>)>>,
+) {
+    if players.is_empty() {
+        commands.remove_resource::<VersusRace>();
+        for entity in versus_ui.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Scores of a new race, kept for the next races until the boards are gone
+pub fn setup_versus_scores(
+    mut commands: Commands,
+    scores: Query<(), With<VersusScores>>,
+    theme: Res<Theme>,
+    localization: Res<Localization>,
+) {
+    if !scores.is_empty() {
+        return;
+    }
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(UI_HEADER_PX),
+                position_type: PositionType::Absolute,
+                top: Val::Px(UI_HEADER_PX),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(VersusScores)
+        .with_children(|parent| {
+            for player in 0..VERSUS_PLAYERS {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.0 / VERSUS_PLAYERS as f32),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: localization.font_for(""),
+                                    font_size: 16.,
+                                    color: theme.text,
+                                },
+                            ))
+                            .insert((VersusScore(player), ThemedText));
+                    });
+            }
+        });
+}
+
+pub fn update_versus_scores(
+    race: Res<VersusRace>,
+    players: Query<(&VersusPlayer, &Puzzle)>,
+    mut scores: Query<(&VersusScore, &mut Text)>,
+    localization: Res<Localization>,
+) {
+    for (player, puzzle) in players.iter() {
+        let time = race.solved_times[player.0].unwrap_or(race.stopwatch.elapsed());
+        let value = localization.text(
+            "versus.score",
+            &[&(player.0 + 1), &puzzle.actions_count, &format_time(time)],
+        );
+        for (_, mut text) in scores.iter_mut().filter(|(score, _)| score.0 == player.0) {
+            if text.sections[0].value != value {
+                set_localized_text(&mut text, value.clone(), &localization);
+            }
+        }
+    }
+}
+
+pub fn show_versus_results(
+    mut commands: Commands,
+    race: Res<VersusRace>,
+    players: Query<(&VersusPlayer, &Puzzle)>,
+    theme: Res<Theme>,
+    localization: Res<Localization>,
+) {
+    let Some(winner) = race.winner else {
+        return;
+    };
+    let mut players = players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(player, _)| player.0);
+    let lines = std::iter::once((localization.text("versus.winner", &[&(winner + 1)]), 32.))
+        .chain(players.into_iter().map(|(player, puzzle)| {
+            let text = match race.solved_times[player.0] {
+                Some(time) => localization.text(
+                    "versus.solved",
+                    &[&(player.0 + 1), &puzzle.actions_count, &format_time(time)],
+                ),
+                None => localization.text(
+                    "versus.unsolved",
+                    &[
+                        &(player.0 + 1),
+                        &puzzle.actions_count,
+                        &((puzzle.progress() * 100.).round() as u32),
+                    ],
+                ),
+            };
+            (text, 22.)
+        }))
+        .collect::<Vec<_>>();
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(VersusResults)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(16.)),
+                        row_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    background_color: theme.header.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (text, font_size) in lines {
+                        parent
+                            .spawn(TextBundle::from_section(
+                                text.clone(),
+                                TextStyle {
+                                    font: localization.font_for(&text),
+                                    font_size,
+                                    color: theme.text,
+                                },
+                            ))
+                            .insert(ThemedText);
+                    }
+                });
+        });
+}
+
+pub fn despawn_versus_results(mut commands: Commands, results: Query<Entity, With<VersusResults>>) {
+    for entity in results.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Minutes, seconds and tenths, like 1:05.3
+fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}